@@ -0,0 +1,253 @@
+use crate::check::CheckRegistry;
+use crate::elf::summary::ElfSummary;
+use crate::error::{Error, Result};
+use crate::{check_summaries, AbiCheckResult};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+// Scratch files are new on every run, so they get no stat entries.
+pub(crate) const SCRATCH_PREFIX: &str = "unrepair-wheel-";
+
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const MAX_BYTES: u64 = 512 * 1024 * 1024;
+
+const KINDS: [&str; 3] = ["elf", "checks", "stat"];
+
+/// On-disk cache of parsed ELF summaries and check results, keyed by content
+/// hash. Opening it evicts old entries of every version.
+pub struct AnalysisCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StatEntry {
+    path: PathBuf,
+    size: u64,
+    mtime_ns: u128,
+    content_hash: String,
+}
+
+impl AnalysisCache {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let base = dir.into();
+        let dir = base.join(concat!("v", env!("CARGO_PKG_VERSION")));
+        fs::create_dir_all(&dir).map_err(Error::io(&dir))?;
+        evict(&base);
+        Ok(Self { dir })
+    }
+
+    /// `$XDG_CACHE_HOME/unrepair`, falling back to `~/.cache/unrepair`.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|base| base.join("unrepair"))
+    }
+
+    pub fn content_hash(&self, path: &Path) -> Result<String> {
+        if is_scratch(path) {
+            return hash_file(path);
+        }
+        let meta = fs::metadata(path).map_err(Error::io(path))?;
+        let mtime_ns = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos());
+        let stat_key = mtime_ns.map(|mtime| {
+            hex(&Sha256::digest(format!(
+                "{}\0{}\0{}",
+                path.display(),
+                meta.len(),
+                mtime
+            )))
+        });
+
+        if let Some(key) = &stat_key {
+            if let Some(entry) = self.load::<StatEntry>("stat", key) {
+                if entry.path == path
+                    && entry.size == meta.len()
+                    && Some(entry.mtime_ns) == mtime_ns
+                {
+                    return Ok(entry.content_hash);
+                }
+            }
+        }
+
+        let content_hash = hash_file(path)?;
+        if let (Some(key), Some(mtime_ns)) = (stat_key, mtime_ns) {
+            self.store(
+                "stat",
+                &key,
+                &StatEntry {
+                    path: path.to_path_buf(),
+                    size: meta.len(),
+                    mtime_ns,
+                    content_hash: content_hash.clone(),
+                },
+            );
+        }
+        Ok(content_hash)
+    }
+
+    pub fn summary(&self, path: &Path) -> Result<ElfSummary> {
+        let hash = self.content_hash(path)?;
+        self.summary_for_hash(path, &hash)
+    }
+
+    /// Cached equivalent of [`crate::check_compatibility_with`].
+    pub fn check(
+        &self,
+        extension: &Path,
+        bundled: &Path,
+        system: &Path,
+        checks: &CheckRegistry,
+    ) -> Result<AbiCheckResult> {
+        let key = self.check_key(extension, bundled, system, checks)?;
+        if let Some(result) = self.cached_check(&key) {
+            return Ok(result);
+        }
+
+        let ext = self.summary(extension)?;
+        let bun = self.summary(bundled)?;
+        let sys = self.summary(system)?;
+        let result = check_summaries(&ext, &bun, &sys, checks)?;
+        self.store_check(&key, &result);
+        Ok(result)
+    }
+
+    pub fn check_key(
+        &self,
+        extension: &Path,
+        bundled: &Path,
+        system: &Path,
+        checks: &CheckRegistry,
+    ) -> Result<String> {
+        // The bundled file name takes part in version matching, so it is part
+        // of the key alongside the content hashes.
+        Ok(hex(&Sha256::digest(format!(
+            "{}\0{}\0{}\0{}\0{}",
+            self.content_hash(extension)?,
+            self.content_hash(bundled)?,
+            bundled.file_name().unwrap_or_default().to_string_lossy(),
+            self.content_hash(system)?,
+            checks.fingerprint()
+        ))))
+    }
+
+    pub fn cached_check(&self, key: &str) -> Option<AbiCheckResult> {
+        self.load("checks", key)
+    }
+
+    pub fn store_check(&self, key: &str, result: &AbiCheckResult) {
+        self.store("checks", key, result);
+    }
+
+    fn summary_for_hash(&self, path: &Path, hash: &str) -> Result<ElfSummary> {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(mut summary) = self.load::<ElfSummary>("elf", hash) {
+            // Identical content may be cached under another file name.
+            summary.file_name = file_name;
+            return Ok(summary);
+        }
+        let summary = ElfSummary::parse(path)?;
+        self.store("elf", hash, &summary);
+        Ok(summary)
+    }
+
+    fn entry_path(&self, kind: &str, key: &str) -> PathBuf {
+        self.dir.join(kind).join(format!("{key}.json"))
+    }
+
+    fn load<T: DeserializeOwned>(&self, kind: &str, key: &str) -> Option<T> {
+        let data = fs::read(self.entry_path(kind, key)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    fn store<T: Serialize>(&self, kind: &str, key: &str, value: &T) {
+        if let Err(err) = self.try_store(kind, key, value) {
+            log::warn!("failed to write cache entry {kind}/{key}: {err}");
+        }
+    }
+
+    fn try_store<T: Serialize>(&self, kind: &str, key: &str, value: &T) -> Result<()> {
+        let dir = self.dir.join(kind);
+        fs::create_dir_all(&dir).map_err(Error::io(&dir))?;
+        let mut tmp = tempfile::NamedTempFile::new_in(&dir).map_err(Error::io(&dir))?;
+        serde_json::to_writer(&mut tmp, value).map_err(|e| Error::io(&dir)(e.into()))?;
+        let path = self.entry_path(kind, key);
+        tmp.persist(&path).map_err(|e| Error::io(&path)(e.error))?;
+        Ok(())
+    }
+}
+
+fn is_scratch(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with(SCRATCH_PREFIX),
+        _ => false,
+    })
+}
+
+/// Drop entries past [`MAX_AGE`], then the oldest until [`MAX_BYTES`] fit.
+fn evict(base: &Path) {
+    let Ok(versions) = fs::read_dir(base) else {
+        return;
+    };
+    let mut entries = Vec::new();
+    for version in versions.flatten() {
+        if !version.file_name().to_string_lossy().starts_with('v') {
+            continue;
+        }
+        for kind in KINDS {
+            let files = WalkDir::new(version.path().join(kind))
+                .max_depth(1)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry.file_type().is_file()
+                        && entry.path().extension().is_some_and(|ext| ext == "json")
+                });
+            for file in files {
+                let Ok(meta) = file.metadata() else {
+                    continue;
+                };
+                let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+                entries.push((modified, meta.len(), file.into_path()));
+            }
+        }
+    }
+    entries.sort();
+
+    let now = SystemTime::now();
+    let mut total = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+    for (modified, len, path) in entries {
+        let expired = now.duration_since(modified).is_ok_and(|age| age > MAX_AGE);
+        if !expired && total <= MAX_BYTES {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
+/// Streaming SHA-256 of a file, hex encoded.
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).map_err(Error::io(path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(Error::io(path))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
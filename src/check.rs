@@ -0,0 +1,166 @@
+use crate::codes::{self, Code};
+use crate::compare::{soname::SonameCheck, symbols::SymbolsCheck, versions::VersionsCheck};
+use crate::elf::summary::ElfSummary;
+use crate::error::{Error, Result};
+use crate::policy::DiagnosticPolicy;
+use crate::Diagnostic;
+use std::collections::BTreeSet;
+
+/// Inputs handed to every check: the three parsed objects and the symbols the
+/// extension actually resolves from the bundled library.
+pub struct CheckContext<'a> {
+    pub extension: &'a ElfSummary,
+    pub bundled: &'a ElfSummary,
+    pub system: &'a ElfSummary,
+    pub used_symbols: &'a BTreeSet<String>,
+}
+
+impl<'a> CheckContext<'a> {
+    pub fn new(
+        extension: &'a ElfSummary,
+        bundled: &'a ElfSummary,
+        system: &'a ElfSummary,
+        used_symbols: &'a BTreeSet<String>,
+    ) -> Self {
+        Self {
+            extension,
+            bundled,
+            system,
+            used_symbols,
+        }
+    }
+}
+
+/// Symbols imported by the extension that the bundled library exports.
+pub fn used_symbols(extension: &ElfSummary, bundled: &ElfSummary) -> BTreeSet<String> {
+    extension
+        .imports
+        .iter()
+        .filter(|sym| bundled.exports.contains_key(*sym))
+        .cloned()
+        .collect()
+}
+
+/// One compatibility rule. Implement this to plug organization-specific
+/// checks into [`CheckRegistry`].
+pub trait Check: Send + Sync {
+    /// Stable name used to select the check (`--checks NAME`).
+    fn name(&self) -> &str;
+
+    /// Codes this check reports beyond [`codes::BUILTIN`], so policy rules
+    /// may name them.
+    fn codes(&self) -> Vec<Code> {
+        Vec::new()
+    }
+
+    fn run(&self, ctx: &CheckContext<'_>) -> anyhow::Result<Vec<Diagnostic>>;
+}
+
+/// Ordered set of checks run by [`crate::check_compatibility_with`], plus the
+/// policy used to adjust the severity of what they report.
+#[derive(Default)]
+pub struct CheckRegistry {
+    checks: Vec<Box<dyn Check>>,
+    policy: DiagnosticPolicy,
+}
+
+impl CheckRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry holding the built-in `elf`, `versions` and `soname` checks.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.checks.push(Box::new(SymbolsCheck));
+        registry.checks.push(Box::new(VersionsCheck));
+        registry.checks.push(Box::new(SonameCheck));
+        registry
+    }
+
+    pub fn register(&mut self, check: impl Check + 'static) -> Result<&mut Self> {
+        if self.get(check.name()).is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "check '{}' is already registered",
+                check.name()
+            )));
+        }
+        self.checks.push(Box::new(check));
+        Ok(self)
+    }
+
+    pub fn with_policy(mut self, policy: DiagnosticPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &DiagnosticPolicy {
+        &self.policy
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Check> {
+        self.checks
+            .iter()
+            .find(|c| c.name() == name)
+            .map(|c| c.as_ref())
+    }
+
+    /// Identifies the selected checks and policy, so cached results are only
+    /// reused for the same configuration.
+    pub fn fingerprint(&self) -> String {
+        format!("{}|{:?}", self.names().join(","), self.policy)
+    }
+
+    /// Built-in codes plus those of the registered checks.
+    pub fn codes(&self) -> Vec<Code> {
+        let mut all = codes::BUILTIN.to_vec();
+        for code in self.checks.iter().flat_map(|c| c.codes()) {
+            if !all.contains(&code) {
+                all.push(code);
+            }
+        }
+        all
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.checks.iter().map(|c| c.name()).collect()
+    }
+
+    /// Keep only the named checks, in registration order. An empty selection
+    /// keeps everything.
+    pub fn select<S: AsRef<str>>(self, names: &[S]) -> Result<Self> {
+        if names.is_empty() {
+            return Ok(self);
+        }
+        for name in names {
+            if self.get(name.as_ref()).is_none() {
+                return Err(Error::InvalidArgument(format!(
+                    "unknown check '{}' (available: {})",
+                    name.as_ref(),
+                    self.names().join(", ")
+                )));
+            }
+        }
+        let checks = self
+            .checks
+            .into_iter()
+            .filter(|c| names.iter().any(|n| n.as_ref() == c.name()))
+            .collect();
+        Ok(Self {
+            checks,
+            policy: self.policy,
+        })
+    }
+
+    pub fn run(&self, ctx: &CheckContext<'_>) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        for check in &self.checks {
+            diagnostics.extend(check.run(ctx).map_err(|source| Error::Check {
+                check: check.name().to_string(),
+                source,
+            })?);
+        }
+        self.policy.apply(&mut diagnostics);
+        Ok(diagnostics)
+    }
+}
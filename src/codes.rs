@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Stable identifier for a kind of diagnostic, e.g. `E001 missing-symbol`.
+///
+/// The letter prefix reflects the default severity; `--allow`, `--warn` and
+/// `--deny` may change the severity but never the code.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Code {
+    pub id: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+}
+
+impl Code {
+    pub const fn new(id: &'static str, name: &'static str) -> Self {
+        Self {
+            id: Cow::Borrowed(id),
+            name: Cow::Borrowed(name),
+        }
+    }
+
+    /// True when `selector` is this code's id or name.
+    pub fn matches(&self, selector: &str) -> bool {
+        self.id.eq_ignore_ascii_case(selector) || self.name == selector
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.id, self.name)
+    }
+}
+
+pub const MISSING_SYMBOL: Code = Code::new("E001", "missing-symbol");
+pub const ELF_HEADER_MISMATCH: Code = Code::new("E002", "elf-header-mismatch");
+pub const MISSING_SYMBOL_VERSION: Code = Code::new("E003", "missing-symbol-version");
+pub const SYMBOL_VERSION_MISMATCH: Code = Code::new("E004", "symbol-version-mismatch");
+pub const SONAME_MISMATCH: Code = Code::new("W010", "soname-mismatch");
+pub const SYMBOL_TYPE_MISMATCH: Code = Code::new("W011", "symbol-type-mismatch");
+
+pub const BUILTIN: &[Code] = &[
+    MISSING_SYMBOL,
+    ELF_HEADER_MISMATCH,
+    MISSING_SYMBOL_VERSION,
+    SYMBOL_VERSION_MISMATCH,
+    SONAME_MISMATCH,
+    SYMBOL_TYPE_MISMATCH,
+];
@@ -1 +1,3 @@
+pub mod soname;
 pub mod symbols;
+pub mod versions;
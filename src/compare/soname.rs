@@ -0,0 +1,29 @@
+use crate::check::{Check, CheckContext};
+use crate::codes;
+use crate::elf::soname;
+use crate::{Diagnostic, Layer, Severity};
+
+/// Warn when the bundled and system libraries disagree on SONAME.
+pub struct SonameCheck;
+
+impl Check for SonameCheck {
+    fn name(&self) -> &str {
+        "soname"
+    }
+
+    fn run(&self, ctx: &CheckContext<'_>) -> anyhow::Result<Vec<Diagnostic>> {
+        Ok(
+            soname::check_soname(&ctx.bundled.soname, &ctx.system.soname)
+                .map(|msg| Diagnostic {
+                    code: codes::SONAME_MISMATCH,
+                    severity: Severity::Warning,
+                    layer: Layer::Elf,
+                    symbol: None,
+                    message: msg,
+                    suppressed: false,
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+}
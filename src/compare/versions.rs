@@ -0,0 +1,54 @@
+use crate::check::{Check, CheckContext};
+use crate::codes;
+use crate::{Diagnostic, Layer, Severity};
+
+/// Symbol versions the extension requires from the bundled library must be
+/// defined by the system library.
+pub struct VersionsCheck;
+
+impl Check for VersionsCheck {
+    fn name(&self) -> &str {
+        "versions"
+    }
+
+    fn run(&self, ctx: &CheckContext<'_>) -> anyhow::Result<Vec<Diagnostic>> {
+        let bundled_ids = ctx.bundled.identities();
+        let mut diagnostics = Vec::new();
+
+        for sym in ctx.used_symbols {
+            let Some(req) = ctx.extension.import_versions.get(sym) else {
+                continue;
+            };
+            if !bundled_ids.contains(&req.library) {
+                continue;
+            }
+            match ctx.system.export_versions.get(sym) {
+                None => diagnostics.push(Diagnostic {
+                    code: codes::MISSING_SYMBOL_VERSION,
+                    severity: Severity::Error,
+                    layer: Layer::Elf,
+                    symbol: Some(sym.clone()),
+                    message: format!(
+                        "System library does not provide required symbol version '{}' (from '{}')",
+                        req.version, req.library
+                    ),
+                    suppressed: false,
+                }),
+                Some(got) if got != &req.version => diagnostics.push(Diagnostic {
+                    code: codes::SYMBOL_VERSION_MISMATCH,
+                    severity: Severity::Error,
+                    layer: Layer::Elf,
+                    symbol: Some(sym.clone()),
+                    message: format!(
+                        "Required symbol version '{}' (from '{}') not satisfied by system (got '{}')",
+                        req.version, req.library, got
+                    ),
+                    suppressed: false,
+                }),
+                Some(_) => {}
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
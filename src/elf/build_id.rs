@@ -0,0 +1,126 @@
+use crate::cache::hex;
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::Path;
+
+const PT_NOTE: u32 = 4;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// GNU build-id of the ELF file at `path`, hex encoded, if it has one.
+pub fn read_build_id(path: &Path) -> Result<Option<String>> {
+    let data = fs::read(path).map_err(Error::io(path))?;
+    Ok(build_id(&data))
+}
+
+/// GNU build-id from the `PT_NOTE` segments of an ELF image. Segments rather
+/// than sections are used so stripped libraries still report it.
+pub fn build_id(data: &[u8]) -> Option<String> {
+    if data.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+    let is_64 = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let reader = Reader {
+        data,
+        little_endian: match data.get(5)? {
+            1 => true,
+            2 => false,
+            _ => return None,
+        },
+    };
+
+    let (phoff, phentsize, phnum) = if is_64 {
+        (reader.u64(0x20)?, reader.u16(0x36)?, reader.u16(0x38)?)
+    } else {
+        (
+            u64::from(reader.u32(0x1c)?),
+            reader.u16(0x2a)?,
+            reader.u16(0x2c)?,
+        )
+    };
+
+    for i in 0..u64::from(phnum) {
+        let header = usize::try_from(phoff.checked_add(i * u64::from(phentsize))?).ok()?;
+        if reader.u32(header)? != PT_NOTE {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (reader.u64(header + 8)?, reader.u64(header + 32)?)
+        } else {
+            (
+                u64::from(reader.u32(header + 4)?),
+                u64::from(reader.u32(header + 16)?),
+            )
+        };
+        let start = usize::try_from(offset).ok()?;
+        let end = usize::try_from(offset.checked_add(size)?).ok()?;
+        if let Some(id) = find_build_id_note(&reader, start, end) {
+            return Some(id);
+        }
+    }
+    None
+}
+
+fn find_build_id_note(reader: &Reader<'_>, mut pos: usize, end: usize) -> Option<String> {
+    while pos.checked_add(12)? <= end {
+        let namesz = reader.u32(pos)? as usize;
+        let descsz = reader.u32(pos + 4)? as usize;
+        let note_type = reader.u32(pos + 8)?;
+        let name_start = pos + 12;
+        let desc_start = name_start.checked_add(align4(namesz))?;
+        let next = desc_start.checked_add(align4(descsz))?;
+
+        if note_type == NT_GNU_BUILD_ID
+            && reader.data.get(name_start..name_start + namesz)? == b"GNU\0"
+        {
+            return Some(hex(reader.data.get(desc_start..desc_start + descsz)?));
+        }
+        pos = next;
+    }
+    None
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, at: usize) -> Option<[u8; N]> {
+        self.data.get(at..at.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.bytes(at)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.bytes(at)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, at: usize) -> Option<u64> {
+        let bytes = self.bytes(at)?;
+        Some(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
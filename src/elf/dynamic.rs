@@ -0,0 +1,102 @@
+use crate::error::{Error, Result};
+use lief::elf::dynamic::Entries;
+use lief::elf::Binary;
+use lief::generic::Section as _;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+pub fn read_needed(path: &Path) -> Result<HashSet<String>> {
+    let binary = Binary::parse(path).ok_or_else(Error::elf_parse(path))?;
+    Ok(extract_needed(&binary).into_iter().collect())
+}
+
+pub fn extract_needed(binary: &Binary) -> Vec<String> {
+    binary
+        .dynamic_entries()
+        .filter_map(|entry| {
+            if let Entries::Library(lib) = entry {
+                Some(lib.name())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Library search path entries in the order the dynamic linker would consult
+/// them: `DT_RUNPATH` when present, otherwise `DT_RPATH`.
+pub fn extract_search_paths(binary: &Binary) -> Vec<String> {
+    let mut runpath = Vec::new();
+    let mut rpath = Vec::new();
+    for entry in binary.dynamic_entries() {
+        match entry {
+            Entries::RunPath(r) => runpath.extend(split_search_path(&r.runpath())),
+            Entries::Rpath(r) => rpath.extend(split_search_path(&r.rpath())),
+            _ => {}
+        }
+    }
+    if runpath.is_empty() {
+        rpath
+    } else {
+        runpath
+    }
+}
+
+/// Sections that hold string literals, such as the arguments of `dlopen`.
+const STRING_SECTIONS: [&str; 3] = [".rodata", ".data.rel.ro", ".data"];
+
+/// Shared library file names that appear as strings in the data sections of
+/// `binary`, sorted: the libraries it may `dlopen`. Only the last component
+/// of path-like strings is kept.
+pub fn extract_dlopen_names(binary: &Binary) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for section in binary.sections() {
+        if !STRING_SECTIONS.contains(&section.name().as_str()) {
+            continue;
+        }
+        for chunk in section.content().split(|&b| b == 0) {
+            let Ok(text) = std::str::from_utf8(chunk) else {
+                continue;
+            };
+            let name = text.rsplit('/').next().unwrap_or(text);
+            if is_library_file_name(name) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+/// Whether `name` looks like `libfoo.so` or `libfoo-1a2b3c4d.so.1.2`.
+fn is_library_file_name(name: &str) -> bool {
+    let Some(idx) = name.find(".so") else {
+        return false;
+    };
+    let (stem, version) = (&name[..idx], &name[idx + 3..]);
+    !stem.is_empty()
+        && stem
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'+'))
+        && (version.is_empty()
+            || version.strip_prefix('.').is_some_and(|rest| {
+                rest.split('.')
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+            }))
+}
+
+pub fn expand_origin(entry: &str, origin: &Path) -> PathBuf {
+    let origin = origin.to_string_lossy();
+    PathBuf::from(
+        entry
+            .replace("${ORIGIN}", &origin)
+            .replace("$ORIGIN", &origin),
+    )
+}
+
+fn split_search_path(value: &str) -> Vec<String> {
+    value
+        .split(':')
+        .filter(|s| !s.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
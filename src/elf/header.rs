@@ -0,0 +1,126 @@
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The bytes every ELF file starts with.
+pub const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const PT_INTERP: u32 = 3;
+
+/// Object file type from `e_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfType {
+    Executable,
+    SharedObject,
+    /// Relocatable objects, core dumps and anything else that is not loaded.
+    Other,
+}
+
+/// What the ELF header and program headers say about a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub elf_type: ElfType,
+    /// Has a `PT_INTERP` segment naming a dynamic linker.
+    pub has_interpreter: bool,
+}
+
+/// Whether `data` starts with [`ELF_MAGIC`].
+pub fn has_elf_magic(data: &[u8]) -> bool {
+    data.starts_with(&ELF_MAGIC)
+}
+
+/// Whether the file at `path` starts with [`ELF_MAGIC`], whatever its name.
+pub fn is_elf_file(path: &Path) -> Result<bool> {
+    let mut file = File::open(path).map_err(Error::io(path))?;
+    let mut magic = [0; 4];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(has_elf_magic(&magic)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(Error::io(path)(err)),
+    }
+}
+
+/// The header of the ELF file at `path`, or `None` when it is not ELF or is
+/// too short for the headers it declares. Only the headers are read.
+pub fn read_header(path: &Path) -> Result<Option<ElfHeader>> {
+    let mut file = File::open(path).map_err(Error::io(path))?;
+    match read_header_from(&mut file) {
+        Ok(header) => Ok(header),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(Error::io(path)(err)),
+    }
+}
+
+fn read_header_from<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ElfHeader>> {
+    let mut ident = [0; 64];
+    reader.read_exact(&mut ident[..ELF_MAGIC.len()])?;
+    if !has_elf_magic(&ident) {
+        return Ok(None);
+    }
+    reader.read_exact(&mut ident[ELF_MAGIC.len()..52])?;
+    let is_64 = ident[4] == 2;
+    if is_64 {
+        reader.read_exact(&mut ident[52..])?;
+    }
+    let big_endian = ident[5] == 2;
+    let u16_at = |offset: usize| {
+        let bytes = [ident[offset], ident[offset + 1]];
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let u32_at = |data: &[u8], offset: usize| {
+        let bytes = [
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ];
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+
+    let elf_type = match u16_at(16) {
+        ET_EXEC => ElfType::Executable,
+        ET_DYN => ElfType::SharedObject,
+        _ => ElfType::Other,
+    };
+    let (phoff, phentsize, phnum) = if is_64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&ident[32..40]);
+        let phoff = if big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        };
+        (phoff, u16_at(54), u16_at(56))
+    } else {
+        (u64::from(u32_at(&ident, 28)), u16_at(42), u16_at(44))
+    };
+    let mut has_interpreter = false;
+    if phentsize >= 4 {
+        let mut entry = vec![0; usize::from(phentsize)];
+        for index in 0..u64::from(phnum) {
+            reader.seek(SeekFrom::Start(
+                phoff.saturating_add(index * u64::from(phentsize)),
+            ))?;
+            reader.read_exact(&mut entry)?;
+            if u32_at(&entry, 0) == PT_INTERP {
+                has_interpreter = true;
+                break;
+            }
+        }
+    }
+    Ok(Some(ElfHeader {
+        elf_type,
+        has_interpreter,
+    }))
+}
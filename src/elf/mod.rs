@@ -1,3 +1,43 @@
+pub mod build_id;
+pub mod dynamic;
+pub mod header;
 pub mod soname;
+pub mod store;
+pub mod summary;
 pub mod symbols;
 pub mod versioning;
+
+use crate::error::{Error, Result};
+use lief::elf::Binary;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+/// An ELF object held in memory, with the file name it is known by.
+///
+/// The name is used in errors and to match version requirements that refer
+/// to a library by file name rather than SONAME.
+#[derive(Debug, Clone, Copy)]
+pub struct ElfBytes<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+}
+
+impl<'a> ElfBytes<'a> {
+    pub fn new(name: &'a str, data: &'a [u8]) -> Self {
+        Self { name, data }
+    }
+
+    pub(crate) fn parse(&self) -> Result<Binary> {
+        parse_reader(&mut Cursor::new(self.data), Path::new(self.name))
+    }
+}
+
+/// Parse an ELF object from any seekable reader. `name` only labels errors.
+pub(crate) fn parse_reader<R: Read + Seek>(reader: &mut R, name: &Path) -> Result<Binary> {
+    match lief::Binary::from(reader) {
+        Some(lief::Binary::ELF(binary)) => Ok(binary),
+        _ => Err(Error::ElfParse {
+            path: name.to_path_buf(),
+        }),
+    }
+}
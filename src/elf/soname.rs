@@ -1,13 +1,18 @@
-use anyhow::{Context, Result};
+use crate::elf::ElfBytes;
+use crate::error::{Error, Result};
 use lief::elf::dynamic::Entries;
 use lief::elf::Binary;
 use std::path::Path;
 
 pub fn extract_soname(path: &Path) -> Result<Option<String>> {
-    let binary = Binary::parse(path).with_context(|| format!("parsing ELF {}", path.display()))?;
+    let binary = Binary::parse(path).ok_or_else(Error::elf_parse(path))?;
     Ok(extract_soname_from_binary(&binary))
 }
 
+pub fn extract_soname_bytes(elf: ElfBytes<'_>) -> Result<Option<String>> {
+    Ok(extract_soname_from_binary(&elf.parse()?))
+}
+
 pub fn extract_soname_from_binary(binary: &Binary) -> Option<String> {
     for entry in binary.dynamic_entries() {
         if let Entries::SharedObject(so) = entry {
@@ -37,3 +42,46 @@ pub fn check_soname(
         _ => None,
     }
 }
+
+pub fn soname_stem(soname: &str) -> Option<String> {
+    soname.find(".so").map(|idx| soname[..idx].to_string())
+}
+
+pub fn soname_prefix_match(vendored_soname: &str, stem: &str) -> bool {
+    if !vendored_soname.starts_with(stem) {
+        return false;
+    }
+    let rest = &vendored_soname[stem.len()..];
+    rest.starts_with('-') || rest.starts_with(".so")
+}
+
+/// Version suffix of a SONAME: `"1.2"` for `libz.so.1.2`, empty when there is
+/// none.
+pub fn soname_version(soname: &str) -> String {
+    soname
+        .find(".so")
+        .map(|idx| soname[idx + 3..].trim_start_matches('.').to_string())
+        .unwrap_or_default()
+}
+
+/// [`soname_stem`] without the `-<8 hex digits>` suffix auditwheel adds to
+/// the libraries it vendors: `libgfortran` for `libgfortran-040039e1.so.5`.
+pub fn unhashed_soname_stem(soname: &str) -> Option<String> {
+    let stem = soname_stem(soname)?;
+    match stem.rsplit_once('-') {
+        Some((name, hash))
+            if !name.is_empty()
+                && hash.len() == 8
+                && hash.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            Some(name.to_string())
+        }
+        _ => Some(stem),
+    }
+}
+
+/// Whether `name` carries the hash suffix auditwheel gives vendored
+/// libraries, as in `libz-a1b2c3d4.so.1`.
+pub fn has_hash_suffix(name: &str) -> bool {
+    unhashed_soname_stem(name) != soname_stem(name)
+}
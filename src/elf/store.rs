@@ -0,0 +1,56 @@
+use crate::cache::AnalysisCache;
+use crate::elf::summary::ElfSummary;
+use crate::error::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+type Slot = Arc<Mutex<Option<Arc<ElfSummary>>>>;
+
+/// Interned ELF summaries for one run: each file is parsed at most once, even
+/// when requested from several threads at the same time.
+pub struct SummaryStore<'c> {
+    cache: Option<&'c AnalysisCache>,
+    slots: Mutex<HashMap<PathBuf, Slot>>,
+}
+
+impl<'c> SummaryStore<'c> {
+    pub fn new(cache: Option<&'c AnalysisCache>) -> Self {
+        Self {
+            cache,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn cache(&self) -> Option<&'c AnalysisCache> {
+        self.cache
+    }
+
+    pub fn get(&self, path: &Path) -> Result<Arc<ElfSummary>> {
+        let slot = {
+            let mut slots = self.slots.lock().expect("summary store lock poisoned");
+            Arc::clone(slots.entry(path.to_path_buf()).or_default())
+        };
+
+        // Holding the slot lock while parsing makes concurrent requests for
+        // the same file wait for the first parse instead of repeating it.
+        let mut summary = slot.lock().expect("summary slot lock poisoned");
+        if let Some(existing) = summary.as_ref() {
+            return Ok(Arc::clone(existing));
+        }
+        let parsed = Arc::new(match self.cache {
+            Some(cache) => cache.summary(path)?,
+            None => ElfSummary::parse(path)?,
+        });
+        *summary = Some(Arc::clone(&parsed));
+        Ok(parsed)
+    }
+
+    /// Forget a file after it was rewritten on disk.
+    pub fn invalidate(&self, path: &Path) {
+        self.slots
+            .lock()
+            .expect("summary store lock poisoned")
+            .remove(path);
+    }
+}
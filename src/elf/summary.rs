@@ -0,0 +1,102 @@
+use crate::elf::symbols::{self, SymbolInfo};
+use crate::elf::versioning::{self, VersionRequirement};
+use crate::elf::{dynamic, soname, ElfBytes};
+use crate::error::{Error, Result};
+use lief::elf::Binary;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// ELF identification fields that must agree for two libraries to be
+/// interchangeable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElfIdentity {
+    pub class: String,
+    pub data: String,
+    pub os_abi: String,
+    pub machine: String,
+}
+
+/// Everything the checks need to know about one ELF file, extracted once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElfSummary {
+    pub file_name: String,
+    pub identity: ElfIdentity,
+    pub soname: Option<String>,
+    pub needed: Vec<String>,
+    /// Library names found in string data, see
+    /// [`dynamic::extract_dlopen_names`].
+    pub dlopen_names: Vec<String>,
+    pub search_paths: Vec<String>,
+    pub imports: BTreeSet<String>,
+    pub exports: BTreeMap<String, SymbolInfo>,
+    pub import_versions: BTreeMap<String, VersionRequirement>,
+    pub export_versions: BTreeMap<String, String>,
+    pub version_definitions: BTreeSet<String>,
+}
+
+impl ElfSummary {
+    pub fn parse(path: &Path) -> Result<Self> {
+        let binary = Binary::parse(path).ok_or_else(Error::elf_parse(path))?;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(Self::from_binary(&binary, &file_name))
+    }
+
+    pub fn from_bytes(elf: ElfBytes<'_>) -> Result<Self> {
+        Ok(Self::from_binary(&elf.parse()?, elf.name))
+    }
+
+    /// Parse from a seekable reader, e.g. a buffered zip entry.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R, file_name: &str) -> Result<Self> {
+        let binary = crate::elf::parse_reader(reader, Path::new(file_name))?;
+        Ok(Self::from_binary(&binary, file_name))
+    }
+
+    pub fn from_binary(binary: &Binary, file_name: &str) -> Self {
+        let header = binary.header();
+        let identity = ElfIdentity {
+            class: format!("{:?}", header.identity_class()),
+            data: format!("{:?}", header.identity_data()),
+            os_abi: format!("{:?}", header.identity_os_abi()),
+            machine: format!("{:?}", header.machine_type()),
+        };
+
+        let imports = symbols::extract_imports(binary);
+        let exports = symbols::extract_exports_with_info(binary);
+        let export_names = exports.keys().cloned().collect::<HashSet<_>>();
+
+        Self {
+            file_name: file_name.to_string(),
+            identity,
+            soname: soname::extract_soname_from_binary(binary),
+            needed: dynamic::extract_needed(binary),
+            dlopen_names: dynamic::extract_dlopen_names(binary),
+            search_paths: dynamic::extract_search_paths(binary),
+            import_versions: versioning::extract_symbol_version_requirements(binary, &imports)
+                .into_iter()
+                .collect(),
+            export_versions: versioning::extract_defined_symbol_versions(binary, &export_names)
+                .into_iter()
+                .collect(),
+            version_definitions: versioning::extract_version_definitions(binary)
+                .into_iter()
+                .collect(),
+            imports: imports.into_iter().collect(),
+            exports: exports.into_iter().collect(),
+        }
+    }
+
+    /// Names this object is known by: its SONAME and its file name.
+    pub fn identities(&self) -> BTreeSet<String> {
+        self.soname
+            .iter()
+            .chain(std::iter::once(&self.file_name))
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect()
+    }
+}
@@ -1,8 +1,9 @@
 use lief::elf::Binary;
 use lief::generic::Symbol;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct VersionRequirement {
     pub library: String,
     pub version: String,
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the `unrepair` library.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be parsed as an ELF object.
+    ElfParse { path: PathBuf },
+    /// `patch::replace_needed` was asked to replace a `DT_NEEDED` entry the
+    /// file does not have.
+    NeededNotFound { path: PathBuf, needed: String },
+    /// A library has no SONAME where one is required.
+    MissingSoname { path: PathBuf },
+    /// The bundled library for an extension could not be determined.
+    Inference { extension: PathBuf, reason: String },
+    /// The wheel is malformed or does not have the expected layout.
+    Wheel { path: PathBuf, reason: String },
+    /// An installed distribution is missing or malformed.
+    Installed { path: PathBuf, reason: String },
+    /// A wheel entry was rejected as unsafe to extract: it escapes the
+    /// output directory, is a symlink, is duplicated or exceeds a limit.
+    UnsafeEntry {
+        path: PathBuf,
+        entry: String,
+        reason: String,
+    },
+    /// The wheel's RECORD does not match its contents.
+    RecordMismatch {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+    /// Reading or writing a file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A zip archive could not be read or written.
+    Zip {
+        path: PathBuf,
+        source: zip::result::ZipError,
+    },
+    /// A JSON config file could not be parsed.
+    Config {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// A wheel plan file could not be parsed.
+    Plan {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// A registered check failed to run.
+    Check {
+        check: String,
+        source: anyhow::Error,
+    },
+    /// An argument passed to the library was rejected.
+    InvalidArgument(String),
+}
+
+impl Error {
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn zip(path: &Path) -> impl FnOnce(zip::result::ZipError) -> Error + '_ {
+        move |source| Error::Zip {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn walk(root: &Path, err: walkdir::Error) -> Error {
+        let path = err
+            .path()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| root.to_path_buf());
+        Error::Io {
+            path,
+            source: err.into(),
+        }
+    }
+
+    pub(crate) fn elf_parse(path: &Path) -> impl FnOnce() -> Error + '_ {
+        move || Error::ElfParse {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub(crate) fn wheel(path: &Path, reason: impl Into<String>) -> Error {
+        Error::Wheel {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn installed(path: &Path, reason: impl Into<String>) -> Error {
+        Error::Installed {
+            path: path.to_path_buf(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Path of the file the error is about, when there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::ElfParse { path }
+            | Error::NeededNotFound { path, .. }
+            | Error::MissingSoname { path }
+            | Error::Wheel { path, .. }
+            | Error::Installed { path, .. }
+            | Error::UnsafeEntry { path, .. }
+            | Error::RecordMismatch { path, .. }
+            | Error::Io { path, .. }
+            | Error::Zip { path, .. }
+            | Error::Config { path, .. }
+            | Error::Plan { path, .. } => Some(path),
+            Error::Inference { extension, .. } => Some(extension),
+            Error::Check { .. } | Error::InvalidArgument(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ElfParse { path } => write!(f, "parsing ELF {}", path.display()),
+            Error::NeededNotFound { path, needed } => write!(
+                f,
+                "DT_NEEDED entry '{}' not found in {}",
+                needed,
+                path.display()
+            ),
+            Error::MissingSoname { path } => {
+                write!(f, "missing SONAME in {}", path.display())
+            }
+            Error::Inference { extension, reason } => write!(
+                f,
+                "cannot infer bundled library for {}: {}",
+                extension.display(),
+                reason
+            ),
+            Error::Wheel { path, reason } => write!(f, "wheel {}: {}", path.display(), reason),
+            Error::Installed { path, reason } => {
+                write!(f, "installed distribution {}: {}", path.display(), reason)
+            }
+            Error::UnsafeEntry {
+                path,
+                entry,
+                reason,
+            } => write!(
+                f,
+                "wheel {}: unsafe entry '{}': {}",
+                path.display(),
+                entry,
+                reason
+            ),
+            Error::RecordMismatch { path, problems } => write!(
+                f,
+                "wheel {}: RECORD does not match the archive: {}",
+                path.display(),
+                problems.join("; ")
+            ),
+            Error::Io { path, .. } => write!(f, "I/O error on {}", path.display()),
+            Error::Zip { path, .. } => write!(f, "zip archive error in {}", path.display()),
+            Error::Config { path, .. } => write!(f, "parsing config {}", path.display()),
+            Error::Plan { path, .. } => write!(f, "parsing plan {}", path.display()),
+            Error::Check { check, .. } => write!(f, "check '{}' failed", check),
+            Error::InvalidArgument(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Zip { source, .. } => Some(source),
+            Error::Config { source, .. } => Some(source),
+            Error::Plan { source, .. } => Some(source),
+            Error::Check { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::elf::{dynamic, soname};
+use crate::error::{Error, Result};
+use lief::elf::Binary;
+use std::path::{Path, PathBuf};
+
+/// Bundled library located by following the extension's dynamic section.
+#[derive(Debug, Clone)]
+pub struct InferredBundled {
+    pub path: PathBuf,
+    pub needed: String,
+}
+
+/// Find the bundled library an extension loads in place of `system`.
+///
+/// The extension's `DT_NEEDED` entries are matched against the SONAME stem of
+/// the system library, and the matching entry is resolved through
+/// `DT_RUNPATH`/`DT_RPATH` (with `$ORIGIN` expanded), falling back to the
+/// extension's own directory.
+pub fn infer_bundled(extension: &Path, system: &Path) -> Result<InferredBundled> {
+    let fail = |reason: String| Error::Inference {
+        extension: extension.to_path_buf(),
+        reason,
+    };
+
+    let system_soname = soname::extract_soname(system)?
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| Error::MissingSoname {
+            path: system.to_path_buf(),
+        })?;
+    let stem = soname::soname_stem(&system_soname).ok_or_else(|| {
+        fail(format!(
+            "system SONAME '{}' does not look like a shared library name",
+            system_soname
+        ))
+    })?;
+
+    let ext_binary = Binary::parse(extension).ok_or_else(Error::elf_parse(extension))?;
+
+    let mut matches = dynamic::extract_needed(&ext_binary)
+        .into_iter()
+        .filter(|needed| soname::soname_prefix_match(needed, &stem))
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+
+    let needed = match matches.len() {
+        0 => {
+            return Err(fail(format!(
+                "no DT_NEEDED entry matches system SONAME stem '{}'",
+                stem
+            )))
+        }
+        1 => matches.remove(0),
+        _ => {
+            return Err(fail(format!(
+                "ambiguous match for system SONAME '{}': extension needs {:?}",
+                system_soname, matches
+            )))
+        }
+    };
+
+    let origin = extension
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut search_dirs = dynamic::extract_search_paths(&ext_binary)
+        .iter()
+        .map(|entry| dynamic::expand_origin(entry, origin))
+        .collect::<Vec<_>>();
+    search_dirs.push(origin.to_path_buf());
+
+    for dir in &search_dirs {
+        let candidate = dir.join(&needed);
+        if candidate.is_file() {
+            return Ok(InferredBundled {
+                path: candidate,
+                needed,
+            });
+        }
+    }
+
+    Err(fail(format!(
+        "could not locate '{}' (searched {:?})",
+        needed, search_dirs
+    )))
+}
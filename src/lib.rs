@@ -1,65 +1,172 @@
+pub mod cache;
+pub mod check;
+pub mod codes;
 pub mod compare;
 pub mod elf;
+pub mod error;
+pub mod infer;
+pub mod parallel;
 pub mod patch;
+pub mod policy;
+pub mod progress;
 pub mod report;
+pub mod wheel;
 
-use anyhow::Result;
-use serde::Serialize;
-use std::path::Path;
+use check::{CheckContext, CheckRegistry};
+use elf::summary::ElfSummary;
+use elf::ElfBytes;
+pub use error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// Entry point for the library's workflows.
+///
+/// ```no_run
+/// let result = unrepair::Unrepair::wheel("dist/mypkg-1.0-cp312-cp312-manylinux_2_28_x86_64.whl")
+///     .system_lib_dir("/usr/lib64")
+///     .strict(true)
+///     .run()?;
+/// assert!(result.is_complete());
+/// # Ok::<(), unrepair::Error>(())
+/// ```
+pub struct Unrepair;
+
+impl Unrepair {
+    /// Unvendor bundled libraries from a wheel file.
+    pub fn wheel(path: impl Into<PathBuf>) -> wheel::WheelBuilder {
+        wheel::WheelBuilder::new(path.into())
+    }
+
+    /// Unvendor bundled libraries from many wheels with shared settings.
+    pub fn wheels<I, P>(paths: I) -> wheel::BatchBuilder
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        wheel::BatchBuilder::new(paths.into_iter().map(Into::into).collect())
+    }
+
+    /// Unvendor bundled libraries from a distribution installed in
+    /// `site_packages`, in place.
+    pub fn installed(
+        site_packages: impl Into<PathBuf>,
+        distribution: impl Into<String>,
+    ) -> wheel::InstalledBuilder {
+        wheel::InstalledBuilder::new(site_packages.into(), distribution.into())
+    }
+
+    /// Report the libraries vendored by every distribution installed in
+    /// `site_packages`, grouped across distributions.
+    pub fn audit_env(site_packages: impl Into<PathBuf>) -> wheel::EnvAuditBuilder {
+        wheel::EnvAuditBuilder::new(site_packages.into())
+    }
+
+    /// Move bundled libraries out of a wheel into a generated library wheel.
+    pub fn split(path: impl Into<PathBuf>) -> wheel::SplitBuilder {
+        wheel::SplitBuilder::new(path.into())
+    }
+
+    /// Copy the system libraries an unvendored wheel depends on back into it.
+    pub fn revendor(path: impl Into<PathBuf>) -> wheel::RevendorBuilder {
+        wheel::RevendorBuilder::new(path.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Verdict {
     Compatible,
     Incompatible,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Severity {
     Info,
     Warning,
     Error,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Layer {
     Elf,
+    /// Diagnostics produced by checks registered outside this crate.
+    Custom,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diagnostic {
+    pub code: codes::Code,
     pub severity: Severity,
     pub layer: Layer,
     pub symbol: Option<String>,
     pub message: String,
+    /// Set when an `allow` rule matched; suppressed diagnostics never affect
+    /// the verdict but remain in JSON output.
+    pub suppressed: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AbiCheckResult {
     pub verdict: Verdict,
     pub diagnostics: Vec<Diagnostic>,
 }
 
+/// Run the built-in checks on one extension/bundled/system triple.
 pub fn check_compatibility(
     extension: &Path,
     bundled: &Path,
     system: &Path,
 ) -> Result<AbiCheckResult> {
-    let mut diagnostics = Vec::new();
+    check_compatibility_with(extension, bundled, system, &CheckRegistry::builtin())
+}
 
-    let (used_symbols, elf_diags) =
-        compare::symbols::check_elf_compatibility(extension, bundled, system)?;
-    diagnostics.extend(elf_diags);
+pub fn check_compatibility_with(
+    extension: &Path,
+    bundled: &Path,
+    system: &Path,
+    checks: &CheckRegistry,
+) -> Result<AbiCheckResult> {
+    let ext = ElfSummary::parse(extension)?;
+    let bun = ElfSummary::parse(bundled)?;
+    let sys = ElfSummary::parse(system)?;
 
-    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
-        return Ok(AbiCheckResult {
-            verdict: Verdict::Incompatible,
-            diagnostics,
-        });
-    }
+    check_summaries(&ext, &bun, &sys, checks)
+}
+
+/// Like [`check_compatibility_with`], for objects already in memory (zip
+/// entries, uploads, other archives).
+pub fn check_compatibility_bytes(
+    extension: ElfBytes<'_>,
+    bundled: ElfBytes<'_>,
+    system: ElfBytes<'_>,
+    checks: &CheckRegistry,
+) -> Result<AbiCheckResult> {
+    let ext = ElfSummary::from_bytes(extension)?;
+    let bun = ElfSummary::from_bytes(bundled)?;
+    let sys = ElfSummary::from_bytes(system)?;
+
+    check_summaries(&ext, &bun, &sys, checks)
+}
+
+pub fn check_summaries(
+    extension: &ElfSummary,
+    bundled: &ElfSummary,
+    system: &ElfSummary,
+    checks: &CheckRegistry,
+) -> Result<AbiCheckResult> {
+    let used_symbols = check::used_symbols(extension, bundled);
+    let ctx = CheckContext::new(extension, bundled, system, &used_symbols);
+    let diagnostics = checks.run(&ctx)?;
 
-    let _ = used_symbols;
+    let verdict = if diagnostics
+        .iter()
+        .any(|d| d.severity == Severity::Error && !d.suppressed)
+    {
+        Verdict::Incompatible
+    } else {
+        Verdict::Compatible
+    };
     Ok(AbiCheckResult {
-        verdict: Verdict::Compatible,
+        verdict,
         diagnostics,
     })
 }
@@ -1,12 +1,14 @@
 use anyhow::Result;
 use clap::builder::styling::{AnsiColor, Effects, Styles};
-use clap::{ColorChoice, Parser, Subcommand, ValueEnum, ValueHint};
-use std::path::{Path, PathBuf};
+use clap::{Args, ColorChoice, Parser, Subcommand, ValueEnum, ValueHint};
+use std::path::PathBuf;
 use std::process;
 
-use unrepair::{check_compatibility, report, Verdict};
-
-mod wheel;
+use unrepair::cache::AnalysisCache;
+use unrepair::check::CheckRegistry;
+use unrepair::policy::{Config, DiagnosticPolicy, Level};
+use unrepair::wheel::{ArchiveLimits, Retag, WheelBuilder, WheelPlan};
+use unrepair::{check_compatibility_with, report, Unrepair, Verdict};
 
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Green.on_default().effects(Effects::BOLD))
@@ -38,6 +40,12 @@ struct Cli {
 enum Commands {
     Check(CheckArgs),
     Wheel(WheelWorkflowArgs),
+    Plan(PlanArgs),
+    Apply(ApplyArgs),
+    Env(EnvArgs),
+    AuditEnv(AuditEnvArgs),
+    Split(SplitArgs),
+    Revendor(RevendorArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -45,7 +53,8 @@ enum Commands {
     about = "Check and optionally patch one extension module against one system library",
     long_about = "Check ABI compatibility between one extension module, one bundled shared \
                   library, and one system shared library. Optionally patch DT_NEEDED when \
-                  the verdict is compatible."
+                  the verdict is compatible. When --bundled is omitted, the bundled library \
+                  is inferred from the extension's DT_NEEDED entries and RUNPATH/RPATH."
 )]
 struct CheckArgs {
     #[arg(
@@ -61,10 +70,11 @@ struct CheckArgs {
         long,
         value_name = "FILE",
         value_hint = ValueHint::FilePath,
-        help = "Path to the bundled shared library",
+        help = "Path to the bundled shared library (inferred from the extension's \
+                DT_NEEDED and RUNPATH/RPATH when omitted)",
         display_order = 2,
     )]
-    bundled: PathBuf,
+    bundled: Option<PathBuf>,
 
     #[arg(
         long,
@@ -101,14 +111,17 @@ struct CheckArgs {
     )]
     output: Option<PathBuf>,
 
-    #[arg(long, short, help = "Enable verbose output", display_order = 7)]
+    #[command(flatten)]
+    selection: CheckSelectionArgs,
+
+    #[arg(long, short, help = "Enable verbose output", display_order = 8)]
     verbose: bool,
 
     #[arg(
         long,
         default_value = "text",
         help = "Output format",
-        display_order = 8
+        display_order = 9
     )]
     format: report::OutputFormat,
 
@@ -117,11 +130,55 @@ struct CheckArgs {
         value_name = "WHEN",
         default_value = "auto",
         help = "Control colored output",
-        display_order = 9
+        display_order = 10
     )]
     color: ColorChoice,
 }
 
+#[derive(Args, Debug)]
+struct CheckSelectionArgs {
+    #[arg(
+        long,
+        value_name = "NAMES",
+        value_delimiter = ',',
+        help = "Comma-separated checks to run (default: all; built-in: elf,versions,soname)"
+    )]
+    checks: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CODE[:GLOB]",
+        help = "Suppress a diagnostic code, optionally only for matching symbols \
+                (repeatable; --warn and --deny take precedence)"
+    )]
+    allow: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CODE[:GLOB]",
+        help = "Report a diagnostic code as a warning (repeatable; overrides --allow, \
+                --deny takes precedence)"
+    )]
+    warn: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CODE[:GLOB]",
+        help = "Report a diagnostic code as an error (repeatable; overrides --allow \
+                and --warn)"
+    )]
+    deny: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "JSON config file with checks/allow/warn/deny settings; \
+                command-line rules override it"
+    )]
+    config: Option<PathBuf>,
+}
+
 #[derive(Parser, Debug)]
 #[command(
     about = "Full wheel workflow: discover, check, unrepair, and repackage",
@@ -131,6 +188,65 @@ struct CheckArgs {
                   bundled libs, and writes a new wheel."
 )]
 struct WheelWorkflowArgs {
+    #[arg(
+        long = "wheel",
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        required_unless_present = "wheels_dir",
+        help = "Input wheel file (.whl); repeat to process several wheels"
+    )]
+    wheels: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with = "output_wheel",
+        help = "Process every .whl file in DIR (repeatable)"
+    )]
+    wheels_dir: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with = "output_wheel",
+        help = "Directory for the output wheels (default: next to each input)"
+    )]
+    output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    input: WheelInputArgs,
+
+    #[command(flatten)]
+    matching: WheelMatchArgs,
+
+    #[command(flatten)]
+    output: WheelOutputArgs,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Plan a wheel unrepair without writing anything",
+    long_about = "Run discovery, matching and checks like `wheel`, then print a JSON plan of \
+                  the pairs, per-extension verdicts, DT_NEEDED edits and files to remove. \
+                  Review the plan, then run `apply` to produce exactly that wheel."
+)]
+struct PlanArgs {
     #[arg(
         long,
         value_name = "FILE",
@@ -139,19 +255,268 @@ struct WheelWorkflowArgs {
     )]
     wheel: PathBuf,
 
+    #[command(flatten)]
+    input: WheelInputArgs,
+
+    #[command(flatten)]
+    matching: WheelMatchArgs,
+
     #[arg(
         long,
+        short,
         value_name = "FILE",
         value_hint = ValueHint::FilePath,
-        help = "Output wheel path (default: <input>.unrepaired.whl)"
+        help = "Write the plan to FILE instead of stdout"
     )]
-    output_wheel: Option<PathBuf>,
+    output: Option<PathBuf>,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Write the wheel described by a reviewed plan",
+    long_about = "Execute a plan made by `plan`: apply its DT_NEEDED edits and removals and \
+                  write the output wheel, without matching or checking again. Fails if the \
+                  wheel or a planned system library changed since the plan was made."
+)]
+struct ApplyArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Plan file written by `unrepair plan`"
+    )]
+    plan: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Input wheel file (.whl)"
+    )]
+    wheel: PathBuf,
+
+    #[command(flatten)]
+    input: WheelInputArgs,
+
+    #[command(flatten)]
+    output: WheelOutputArgs,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Unvendor bundled libraries from an installed distribution in place",
+    long_about = "Apply the wheel workflow to a distribution already installed in a \
+                  site-packages directory: patch its extension modules, delete the bundled \
+                  libraries nothing needs any more, and rewrite its RECORD to match."
+)]
+struct EnvArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "site-packages directory the distribution is installed in"
+    )]
+    site_packages: PathBuf,
+
+    #[arg(long, value_name = "NAME", help = "Name of the installed distribution")]
+    dist: String,
+
+    #[command(flatten)]
+    matching: WheelMatchArgs,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Directory where temporary copies of the shared objects should be created"
+    )]
+    workdir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Continue when the installed RECORD does not match the installed files"
+    )]
+    ignore_record_errors: bool,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Report libraries vendored across an environment and optionally unvendor them",
+    long_about = "Scan every distribution installed in a site-packages directory, group the \
+                  libraries they vendor by SONAME stem and version, and report duplication and \
+                  disk usage. With --unvendor, apply the env workflow to every distribution \
+                  whose vendored libraries match one of the given system libraries."
+)]
+struct AuditEnvArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "site-packages directory to scan"
+    )]
+    site_packages: PathBuf,
+
+    #[arg(
+        long,
+        help = "Unvendor every distribution with a matching system library, updating each RECORD"
+    )]
+    unvendor: bool,
+
+    #[command(flatten)]
+    matching: WheelMatchArgs,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Directory where temporary copies of the shared objects should be created"
+    )]
+    workdir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Continue when an installed RECORD does not match the installed files"
+    )]
+    ignore_record_errors: bool,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Move bundled libraries into a separate generated wheel",
+    long_about = "Move selected bundled libraries out of a wheel into a generated library \
+                  wheel with its own dist-info and RECORD. Objects that need them get a \
+                  RUNPATH entry pointing at the library wheel's install location, and the \
+                  output wheel's METADATA gains a Requires-Dist on it."
+)]
+struct SplitArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Input wheel file (.whl)"
+    )]
+    wheel: PathBuf,
+
+    #[arg(
+        long = "lib",
+        value_name = "NAME",
+        help = "SONAME, file name or wheel path of a bundled library to move \
+                (repeatable; default: all)"
+    )]
+    libs: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Distribution name of the generated wheel (default: <name>-libs)"
+    )]
+    libs_name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with = "output_wheel",
+        help = "Directory for both output wheels (default: next to the output wheel)"
+    )]
+    output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    input: WheelInputArgs,
+
+    #[command(flatten)]
+    output: WheelOutputArgs,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Copy system libraries back into an unvendored wheel",
+    long_about = "Undo unvendoring: copy the system libraries a wheel's extensions now need \
+                  into a .libs directory under hashed names, point DT_NEEDED and RUNPATH at \
+                  the copies, and restore the original version and platform tag. Uses the \
+                  wheel's unrepair.json when present, otherwise its Requires-External headers."
+)]
+struct RevendorArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Input wheel file (.whl)"
+    )]
+    wheel: PathBuf,
 
     #[arg(
         long = "system-lib",
         value_name = "FILE",
         value_hint = ValueHint::FilePath,
-        help = "System library candidate file (repeatable)"
+        help = "System library to vendor when the recorded one is missing or changed (repeatable)"
     )]
     system_lib: Vec<PathBuf>,
 
@@ -163,6 +528,45 @@ struct WheelWorkflowArgs {
     )]
     system_lib_dir: Vec<PathBuf>,
 
+    #[command(flatten)]
+    providers: ProviderArgs,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Output wheel path (default: restored wheel name next to the input)"
+    )]
+    output_wheel: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        help = "Platform tag for the output wheel instead of the restored one"
+    )]
+    platform_tag: Option<String>,
+
+    #[command(flatten)]
+    input: WheelInputArgs,
+
+    #[arg(long, short, help = "Enable verbose output")]
+    verbose: bool,
+
+    #[arg(long, default_value = "text", help = "Output format")]
+    format: report::OutputFormat,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Control colored output"
+    )]
+    color: ColorChoice,
+}
+
+/// How input wheels are read.
+#[derive(Args, Debug)]
+struct WheelInputArgs {
     #[arg(
         long,
         value_name = "DIR",
@@ -171,6 +575,91 @@ struct WheelWorkflowArgs {
     )]
     workdir: Option<PathBuf>,
 
+    #[arg(
+        long,
+        help = "Continue when the input RECORD does not match the wheel contents"
+    )]
+    ignore_record_errors: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Reject wheels with more entries than this"
+    )]
+    max_entries: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Reject wheels with an entry larger than this when decompressed"
+    )]
+    max_entry_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Reject wheels larger than this when decompressed"
+    )]
+    max_total_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Reject entries over 1 MiB that compress better than N:1"
+    )]
+    max_ratio: Option<u64>,
+}
+
+/// Distributions whose libraries stand in for system libraries.
+#[derive(Args, Debug)]
+struct ProviderArgs {
+    #[arg(
+        long = "provider-wheel",
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Wheel whose libraries may replace bundled ones; the output requires it (repeatable)"
+    )]
+    provider_wheel: Vec<PathBuf>,
+
+    #[arg(
+        long = "provider-dist",
+        value_name = "NAME",
+        requires = "provider_site_packages",
+        help = "Installed distribution whose libraries may replace bundled ones (repeatable)"
+    )]
+    provider_dist: Vec<String>,
+
+    #[arg(
+        long = "provider-site-packages",
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "site-packages directory the --provider-dist distributions are installed in"
+    )]
+    provider_site_packages: Option<PathBuf>,
+}
+
+/// How bundled libraries are matched to system libraries and checked.
+#[derive(Args, Debug)]
+struct WheelMatchArgs {
+    #[arg(
+        long = "system-lib",
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "System library candidate file (repeatable)"
+    )]
+    system_lib: Vec<PathBuf>,
+
+    #[arg(
+        long = "system-lib-dir",
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Directory to recursively scan for system libraries (repeatable)"
+    )]
+    system_lib_dir: Vec<PathBuf>,
+
+    #[command(flatten)]
+    providers: ProviderArgs,
+
     #[arg(
         long = "no-strict",
         action = clap::ArgAction::SetFalse,
@@ -179,19 +668,77 @@ struct WheelWorkflowArgs {
     )]
     strict: bool,
 
-    #[arg(long, short, help = "Enable verbose output")]
-    verbose: bool,
+    #[arg(
+        long,
+        help = "Write nothing unless every requested unrepair action succeeds"
+    )]
+    transactional: bool,
 
-    #[arg(long, default_value = "text", help = "Output format")]
-    format: report::OutputFormat,
+    #[arg(
+        long,
+        help = "Only unvendor a library if every extension using it passes the checks"
+    )]
+    all_consumers: bool,
 
     #[arg(
         long,
-        value_name = "WHEN",
-        default_value = "auto",
-        help = "Control colored output"
+        help = "Also remove bundled libraries nothing else reaches via DT_NEEDED or dlopen names"
     )]
-    color: ColorChoice,
+    prune_unused: bool,
+
+    #[command(flatten)]
+    selection: CheckSelectionArgs,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with = "no_cache",
+        help = "Directory for the persistent analysis cache (default: ~/.cache/unrepair)"
+    )]
+    cache_dir: Option<PathBuf>,
+
+    #[arg(long, help = "Do not read or write the analysis cache")]
+    no_cache: bool,
+
+    #[arg(
+        long,
+        short = 'j',
+        value_name = "N",
+        help = "Worker threads for parsing and checking (default: available CPUs)"
+    )]
+    jobs: Option<usize>,
+}
+
+/// Name and labelling of the output wheel.
+#[derive(Args, Debug)]
+struct WheelOutputArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Output wheel path (default: relabelled wheel name next to the input)"
+    )]
+    output_wheel: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        help = "Platform tag for the output wheel; 'linux' turns manylinux/musllinux tags into linux_<arch>"
+    )]
+    platform_tag: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LABEL",
+        conflicts_with = "no_local_version",
+        help = "Local version label appended to the output wheel version \
+                (default: unrepaired; split: split)"
+    )]
+    local_version: Option<String>,
+
+    #[arg(long, help = "Keep the input wheel version unchanged")]
+    no_local_version: bool,
 }
 
 fn main() -> Result<()> {
@@ -201,12 +748,31 @@ fn main() -> Result<()> {
     match cli.command {
         Commands::Check(args) => run_check(args),
         Commands::Wheel(args) => run_wheel(args),
+        Commands::Plan(args) => run_plan(args),
+        Commands::Apply(args) => run_apply(args),
+        Commands::Env(args) => run_env(args),
+        Commands::AuditEnv(args) => run_audit_env(args),
+        Commands::Split(args) => run_split(args),
+        Commands::Revendor(args) => run_revendor(args),
     }
 }
 
 fn run_check(args: CheckArgs) -> Result<()> {
     let color_choice = to_color_mode(args.color);
-    let result = check_compatibility(&args.extension, &args.bundled, &args.system)?;
+    let bundled = match args.bundled {
+        Some(path) => path,
+        None => {
+            let inferred = unrepair::infer::infer_bundled(&args.extension, &args.system)?;
+            eprintln!(
+                "Inferred bundled library: {} (DT_NEEDED {})",
+                inferred.path.display(),
+                inferred.needed
+            );
+            inferred.path
+        }
+    };
+    let checks = build_checks(&args.selection)?;
+    let result = check_compatibility_with(&args.extension, &bundled, &args.system, &checks)?;
 
     match args.format {
         report::OutputFormat::Text => report::print_text(&result, args.verbose, color_choice),
@@ -214,7 +780,7 @@ fn run_check(args: CheckArgs) -> Result<()> {
     }
 
     if args.patch && result.verdict == Verdict::Compatible {
-        let bundled_soname = unrepair::elf::soname::extract_soname(&args.bundled)?;
+        let bundled_soname = unrepair::elf::soname::extract_soname(&bundled)?;
         let old_lib = bundled_soname.unwrap_or_default();
         if old_lib.is_empty() {
             eprintln!("Error: Cannot patch - missing SONAME in bundled library");
@@ -249,27 +815,263 @@ fn run_check(args: CheckArgs) -> Result<()> {
 }
 
 fn run_wheel(args: WheelWorkflowArgs) -> Result<()> {
+    if args.wheels.len() != 1 || !args.wheels_dir.is_empty() || args.output_dir.is_some() {
+        return run_wheel_batch(args);
+    }
+    let color_mode = to_color_mode(args.color);
+    let builder = Unrepair::wheel(&args.wheels[0])
+        .progress(report::StderrProgress::new(color_mode, args.verbose));
+    let builder = configure_input(builder, &args.input);
+    let builder = configure_matching(builder, &args.matching)?;
+    let result = configure_output(builder, &args.output).run()?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_wheel_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_wheel_json(&result)?,
+    }
+    process::exit(i32::from(!result.is_complete()));
+}
+
+fn run_wheel_batch(args: WheelWorkflowArgs) -> Result<()> {
+    if args.output.output_wheel.is_some() {
+        anyhow::bail!("--output-wheel needs a single --wheel; use --output-dir for several");
+    }
+    let color_mode = to_color_mode(args.color);
+    let options =
+        WheelBuilder::default().progress(report::StderrProgress::new(color_mode, args.verbose));
+    let options = configure_input(options, &args.input);
+    let options = configure_matching(options, &args.matching)?;
+    let mut batch = Unrepair::wheels(&args.wheels).options(configure_output(options, &args.output));
+    for dir in &args.wheels_dir {
+        batch = batch.wheels_dir(dir);
+    }
+    if let Some(dir) = &args.output_dir {
+        batch = batch.output_dir(dir);
+    }
+    let result = batch.run()?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_batch_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_batch_json(&result)?,
+    }
+    process::exit(i32::from(!result.is_complete()));
+}
+
+fn run_plan(args: PlanArgs) -> Result<()> {
+    let color_mode = to_color_mode(args.color);
+    let builder = Unrepair::wheel(&args.wheel)
+        .progress(report::StderrProgress::new(color_mode, args.verbose));
+    let builder = configure_input(builder, &args.input);
+    let plan = configure_matching(builder, &args.matching)?.plan()?;
+
+    match &args.output {
+        Some(path) => plan.save(path)?,
+        None => print!("{}", plan.to_json()),
+    }
+    report::print_plan_text(&plan, color_mode);
+    process::exit(i32::from(!plan.is_complete()));
+}
+
+fn run_apply(args: ApplyArgs) -> Result<()> {
     let color_mode = to_color_mode(args.color);
-    let output_wheel = args
-        .output_wheel
-        .unwrap_or_else(|| default_output_wheel(&args.wheel));
-
-    let result = wheel::run(wheel::WheelArgs {
-        wheel: &args.wheel,
-        output_wheel: &output_wheel,
-        system_libs: &args.system_lib,
-        system_lib_dirs: &args.system_lib_dir,
-        strict: args.strict,
-        color_mode,
-        verbose: args.verbose,
-        workdir: args.workdir.as_deref(),
-    })?;
+    let plan = WheelPlan::load(&args.plan)?;
+    let builder = Unrepair::wheel(&args.wheel)
+        .progress(report::StderrProgress::new(color_mode, args.verbose));
+    let builder = configure_input(builder, &args.input);
+    let result = configure_output(builder, &args.output).apply(&plan)?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_wheel_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_wheel_json(&result)?,
+    }
+    process::exit(i32::from(!result.is_complete()));
+}
+
+fn run_env(args: EnvArgs) -> Result<()> {
+    let color_mode = to_color_mode(args.color);
+    let mut options = WheelBuilder::default()
+        .progress(report::StderrProgress::new(color_mode, args.verbose))
+        .ignore_record_errors(args.ignore_record_errors);
+    if let Some(workdir) = &args.workdir {
+        options = options.workdir(workdir);
+    }
+    let options = configure_matching(options, &args.matching)?;
+    let result = Unrepair::installed(&args.site_packages, &args.dist)
+        .options(options)
+        .run()?;
 
     match args.format {
-        report::OutputFormat::Text => wheel::print_text(&result, color_mode),
-        report::OutputFormat::Json => wheel::print_json(&result)?,
+        report::OutputFormat::Text => report::print_installed_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_installed_json(&result)?,
     }
-    process::exit(wheel::exit_code(&result));
+    process::exit(i32::from(!result.is_complete()));
+}
+
+fn run_audit_env(args: AuditEnvArgs) -> Result<()> {
+    let color_mode = to_color_mode(args.color);
+    let mut options = WheelBuilder::default()
+        .progress(report::StderrProgress::new(color_mode, args.verbose))
+        .ignore_record_errors(args.ignore_record_errors);
+    if let Some(workdir) = &args.workdir {
+        options = options.workdir(workdir);
+    }
+    let options = configure_matching(options, &args.matching)?;
+    let audit = Unrepair::audit_env(&args.site_packages)
+        .unvendor(args.unvendor)
+        .options(options)
+        .run()?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_audit_text(&audit, color_mode),
+        report::OutputFormat::Json => report::print_audit_json(&audit)?,
+    }
+    process::exit(i32::from(!audit.is_complete()));
+}
+
+fn run_split(args: SplitArgs) -> Result<()> {
+    let color_mode = to_color_mode(args.color);
+    let options =
+        WheelBuilder::default().progress(report::StderrProgress::new(color_mode, args.verbose));
+    let options = configure_output(configure_input(options, &args.input), &args.output);
+    let mut split = Unrepair::split(&args.wheel).options(options);
+    for lib in &args.libs {
+        split = split.lib(lib);
+    }
+    if let Some(name) = &args.libs_name {
+        split = split.libs_name(name);
+    }
+    if let Some(dir) = &args.output_dir {
+        split = split.output_dir(dir);
+    }
+    let result = split.run()?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_split_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_split_json(&result)?,
+    }
+    Ok(())
+}
+
+fn run_revendor(args: RevendorArgs) -> Result<()> {
+    let color_mode = to_color_mode(args.color);
+    let mut options = configure_input(
+        WheelBuilder::default().progress(report::StderrProgress::new(color_mode, args.verbose)),
+        &args.input,
+    )
+    .system_libs(&args.system_lib)
+    .system_lib_dirs(&args.system_lib_dir);
+    options = configure_providers(options, &args.providers);
+    if let Some(output_wheel) = &args.output_wheel {
+        options = options.output_wheel(output_wheel);
+    }
+    if let Some(tag) = &args.platform_tag {
+        options = options.retag(Retag::Platform(tag.clone()));
+    }
+    let result = Unrepair::revendor(&args.wheel).options(options).run()?;
+
+    match args.format {
+        report::OutputFormat::Text => report::print_revendor_text(&result, color_mode),
+        report::OutputFormat::Json => report::print_revendor_json(&result)?,
+    }
+    Ok(())
+}
+
+fn configure_providers(mut builder: WheelBuilder, args: &ProviderArgs) -> WheelBuilder {
+    for wheel in &args.provider_wheel {
+        builder = builder.provider_wheel(wheel);
+    }
+    if let Some(site_packages) = &args.provider_site_packages {
+        for dist in &args.provider_dist {
+            builder = builder.provider_installed(site_packages, dist);
+        }
+    }
+    builder
+}
+
+fn configure_input(mut builder: WheelBuilder, args: &WheelInputArgs) -> WheelBuilder {
+    if let Some(workdir) = &args.workdir {
+        builder = builder.workdir(workdir);
+    }
+    let mut limits = ArchiveLimits::default();
+    if let Some(max) = args.max_entries {
+        limits.max_entries = max;
+    }
+    if let Some(max) = args.max_entry_size {
+        limits.max_entry_size = max;
+    }
+    if let Some(max) = args.max_total_size {
+        limits.max_total_size = max;
+    }
+    if let Some(max) = args.max_ratio {
+        limits.max_ratio = max;
+    }
+    builder
+        .limits(limits)
+        .ignore_record_errors(args.ignore_record_errors)
+}
+
+fn configure_matching(mut builder: WheelBuilder, args: &WheelMatchArgs) -> Result<WheelBuilder> {
+    builder = builder
+        .system_libs(&args.system_lib)
+        .system_lib_dirs(&args.system_lib_dir)
+        .strict(args.strict)
+        .transactional(args.transactional)
+        .all_consumers(args.all_consumers)
+        .prune_unused(args.prune_unused)
+        .checks(build_checks(&args.selection)?);
+    builder = configure_providers(builder, &args.providers);
+    if let Some(jobs) = args.jobs {
+        builder = builder.jobs(jobs);
+    }
+    if !args.no_cache {
+        if let Some(dir) = args.cache_dir.clone().or_else(AnalysisCache::default_dir) {
+            builder = builder.cache(AnalysisCache::open(dir)?);
+        }
+    }
+    Ok(builder)
+}
+
+fn configure_output(mut builder: WheelBuilder, args: &WheelOutputArgs) -> WheelBuilder {
+    if let Some(output_wheel) = &args.output_wheel {
+        builder = builder.output_wheel(output_wheel);
+    }
+    match args.platform_tag.as_deref() {
+        None => {}
+        Some("linux") => builder = builder.retag(Retag::Linux),
+        Some(tag) => builder = builder.retag(Retag::Platform(tag.to_string())),
+    }
+    if args.no_local_version {
+        builder = builder.no_local_version();
+    }
+    if let Some(label) = &args.local_version {
+        builder = builder.local_version(label);
+    }
+    builder
+}
+
+fn build_checks(args: &CheckSelectionArgs) -> Result<CheckRegistry> {
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let names = if args.checks.is_empty() {
+        &config.checks
+    } else {
+        &args.checks
+    };
+
+    let registry = CheckRegistry::builtin();
+    let mut policy = DiagnosticPolicy::new();
+    for code in registry.codes() {
+        policy.register_code(code);
+    }
+    config.extend_policy(&mut policy)?;
+    policy
+        .extend_from_specs(Level::Allow, &args.allow)?
+        .extend_from_specs(Level::Warn, &args.warn)?
+        .extend_from_specs(Level::Deny, &args.deny)?;
+
+    Ok(registry.select(names)?.with_policy(policy))
 }
 
 fn to_color_mode(choice: ColorChoice) -> report::ColorMode {
@@ -279,12 +1081,3 @@ fn to_color_mode(choice: ColorChoice) -> report::ColorMode {
         ColorChoice::Never => report::ColorMode::Never,
     }
 }
-
-fn default_output_wheel(input: &Path) -> PathBuf {
-    let parent = input.parent().unwrap_or_else(|| Path::new("."));
-    let stem = input
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("output");
-    parent.join(format!("{stem}.unrepaired.whl"))
-}
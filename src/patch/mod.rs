@@ -1,4 +1,7 @@
-use anyhow::{bail, Context, Result};
+use crate::elf::dynamic::extract_search_paths;
+use crate::elf::ElfBytes;
+use crate::error::{Error, Result};
+use lief::elf::dynamic::{self, Tag};
 use lief::elf::Binary;
 use std::path::Path;
 
@@ -8,16 +11,124 @@ pub fn replace_needed(
     old_lib: &str,
     new_lib: &str,
 ) -> Result<()> {
-    if old_lib.is_empty() || new_lib.is_empty() {
-        bail!("library names must be non-empty");
+    check_library_names(old_lib, new_lib)?;
+
+    let mut elf = Binary::parse(elf_path).ok_or_else(Error::elf_parse(elf_path))?;
+    rename_needed(&mut elf, elf_path, old_lib, new_lib)?;
+
+    elf.write(output_path);
+
+    std::fs::metadata(output_path).map_err(Error::io(output_path))?;
+
+    Ok(())
+}
+
+/// Like [`replace_needed`], for an object held in memory. Returns the patched
+/// bytes.
+pub fn replace_needed_bytes(elf: ElfBytes<'_>, old_lib: &str, new_lib: &str) -> Result<Vec<u8>> {
+    check_library_names(old_lib, new_lib)?;
+
+    let mut binary = elf.parse()?;
+    rename_needed(&mut binary, Path::new(elf.name), old_lib, new_lib)?;
+
+    // LIEF only writes to paths, so round-trip through a scratch file.
+    let scratch = tempfile::Builder::new()
+        .prefix("unrepair-patch-")
+        .tempdir()
+        .map_err(Error::io(&std::env::temp_dir()))?;
+    let output_path = scratch.path().join("patched.so");
+    binary.write(&output_path);
+    std::fs::read(&output_path).map_err(Error::io(&output_path))
+}
+
+/// Append `paths` to the library search path of `elf_path`, skipping those
+/// already present, and write the result to `output_path`. An existing
+/// `DT_RPATH` is carried over into the new `DT_RUNPATH` so its entries keep
+/// being searched.
+pub fn add_runpath(elf_path: &Path, output_path: &Path, paths: &[String]) -> Result<()> {
+    if paths
+        .iter()
+        .any(|path| path.is_empty() || path.contains(':'))
+    {
+        return Err(Error::InvalidArgument(
+            "RUNPATH entries must be non-empty and must not contain ':'".to_string(),
+        ));
+    }
+
+    let mut elf = Binary::parse(elf_path).ok_or_else(Error::elf_parse(elf_path))?;
+    let mut search_paths = extract_search_paths(&elf);
+    for path in paths {
+        if !search_paths.contains(path) {
+            search_paths.push(path.clone());
+        }
+    }
+    elf.remove_dynamic_entries_by_tag(Tag::RPATH);
+    elf.remove_dynamic_entries_by_tag(Tag::RUNPATH);
+    elf.add_dynamic_entry(&dynamic::RunPath::new(&search_paths.join(":")));
+
+    elf.write(output_path);
+
+    std::fs::metadata(output_path).map_err(Error::io(output_path))?;
+
+    Ok(())
+}
+
+/// Remove `paths` from the library search path of `elf_path` and write the
+/// result to `output_path`. Like [`add_runpath`], an existing `DT_RPATH` is
+/// folded into `DT_RUNPATH`; neither is written when no entry is left.
+pub fn remove_runpath(elf_path: &Path, output_path: &Path, paths: &[String]) -> Result<()> {
+    let mut elf = Binary::parse(elf_path).ok_or_else(Error::elf_parse(elf_path))?;
+    let mut search_paths = extract_search_paths(&elf);
+    search_paths.retain(|path| !paths.contains(path));
+    elf.remove_dynamic_entries_by_tag(Tag::RPATH);
+    elf.remove_dynamic_entries_by_tag(Tag::RUNPATH);
+    if !search_paths.is_empty() {
+        elf.add_dynamic_entry(&dynamic::RunPath::new(&search_paths.join(":")));
     }
 
-    let mut elf =
-        Binary::parse(elf_path).with_context(|| format!("parsing ELF {}", elf_path.display()))?;
+    elf.write(output_path);
+
+    std::fs::metadata(output_path).map_err(Error::io(output_path))?;
+
+    Ok(())
+}
+
+/// Set the `DT_SONAME` of `elf_path` to `soname`, adding one if it has none,
+/// and write the result to `output_path`.
+pub fn set_soname(elf_path: &Path, output_path: &Path, soname: &str) -> Result<()> {
+    if soname.is_empty() {
+        return Err(Error::InvalidArgument(
+            "SONAME must be non-empty".to_string(),
+        ));
+    }
+
+    let mut elf = Binary::parse(elf_path).ok_or_else(Error::elf_parse(elf_path))?;
+    elf.remove_dynamic_entries_by_tag(Tag::SONAME);
+    elf.add_dynamic_entry(&dynamic::SharedObject::new(soname));
+
+    elf.write(output_path);
+
+    std::fs::metadata(output_path).map_err(Error::io(output_path))?;
 
+    Ok(())
+}
+
+fn check_library_names(old_lib: &str, new_lib: &str) -> Result<()> {
+    if old_lib.is_empty() || new_lib.is_empty() {
+        return Err(Error::InvalidArgument(
+            "library names must be non-empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn rename_needed(elf: &mut Binary, elf_path: &Path, old_lib: &str, new_lib: &str) -> Result<()> {
     let mut needed = elf
         .get_library(old_lib)
-        .with_context(|| format!("DT_NEEDED entry '{}' not found", old_lib))?;
+        .ok_or_else(|| Error::NeededNotFound {
+            path: elf_path.to_path_buf(),
+            needed: old_lib.to_string(),
+        })?;
     needed.set_name(new_lib);
 
     // Also patch the VERNEED entry so the dynamic linker can match version
@@ -27,11 +138,5 @@ pub fn replace_needed(
     if let Some(mut verneed) = elf.find_version_requirement(old_lib) {
         verneed.set_name(new_lib);
     }
-
-    elf.write(output_path);
-
-    std::fs::metadata(output_path)
-        .with_context(|| format!("writing patched binary to {}", output_path.display()))?;
-
     Ok(())
 }
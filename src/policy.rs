@@ -0,0 +1,195 @@
+use crate::codes::{self, Code};
+use crate::error::{Error, Result};
+use crate::{Diagnostic, Severity};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// `CODE[:symbol-glob]`, where `CODE` is an id (`W010`) or name (`soname-mismatch`).
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub level: Level,
+    pub code: String,
+    pub symbol: Option<String>,
+}
+
+impl Rule {
+    pub fn parse(level: Level, spec: &str) -> Result<Self> {
+        Self::parse_known(level, spec, codes::BUILTIN)
+    }
+
+    /// Like [`Rule::parse`], for codes in `known` instead of the built-ins.
+    pub fn parse_known(level: Level, spec: &str, known: &[Code]) -> Result<Self> {
+        let (code, symbol) = match spec.split_once(':') {
+            Some((code, glob)) => (code, Some(glob.to_string())),
+            None => (spec, None),
+        };
+        if code.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "empty diagnostic code in '{}'",
+                spec
+            )));
+        }
+        if !known.iter().any(|known| known.matches(code)) {
+            return Err(Error::InvalidArgument(format!(
+                "unknown diagnostic code '{}' (known: {})",
+                code,
+                known
+                    .iter()
+                    .map(|known| known.id.as_ref())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(Self {
+            level,
+            code: code.to_string(),
+            symbol,
+        })
+    }
+
+    fn matches(&self, diag: &Diagnostic) -> bool {
+        if !diag.code.matches(&self.code) {
+            return false;
+        }
+        match (&self.symbol, &diag.symbol) {
+            (None, _) => true,
+            (Some(glob), Some(sym)) => glob_match(glob, sym),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Severity overrides; when several rules match, the last one pushed wins.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticPolicy {
+    rules: Vec<Rule>,
+    codes: Vec<Code>,
+}
+
+impl DiagnosticPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, rule: Rule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Accept a custom check's code in specs.
+    pub fn register_code(&mut self, code: Code) -> &mut Self {
+        if !self.codes.contains(&code) {
+            self.codes.push(code);
+        }
+        self
+    }
+
+    pub fn extend_from_specs(&mut self, level: Level, specs: &[String]) -> Result<&mut Self> {
+        let known = codes::BUILTIN
+            .iter()
+            .chain(&self.codes)
+            .cloned()
+            .collect::<Vec<_>>();
+        for spec in specs {
+            self.push(Rule::parse_known(level, spec, &known)?);
+        }
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn apply(&self, diagnostics: &mut [Diagnostic]) {
+        for diag in diagnostics {
+            let Some(rule) = self.rules.iter().rev().find(|r| r.matches(diag)) else {
+                continue;
+            };
+            match rule.level {
+                Level::Allow => diag.suppressed = true,
+                Level::Warn => {
+                    diag.suppressed = false;
+                    diag.severity = Severity::Warning;
+                }
+                Level::Deny => {
+                    diag.suppressed = false;
+                    diag.severity = Severity::Error;
+                }
+            }
+        }
+    }
+}
+
+/// Settings that can live in a JSON config file instead of on the command line.
+///
+/// ```json
+/// { "checks": ["elf", "versions"], "allow": ["W010"], "deny": ["W011:png_*"] }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub checks: Vec<String>,
+    pub allow: Vec<String>,
+    pub warn: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(Error::io(path))?;
+        serde_json::from_str(&text).map_err(|source| Error::Config {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn policy(&self) -> Result<DiagnosticPolicy> {
+        let mut policy = DiagnosticPolicy::new();
+        self.extend_policy(&mut policy)?;
+        Ok(policy)
+    }
+
+    /// Adds allow, then warn, then deny rules, so deny takes precedence.
+    pub fn extend_policy(&self, policy: &mut DiagnosticPolicy) -> Result<()> {
+        policy
+            .extend_from_specs(Level::Allow, &self.allow)?
+            .extend_from_specs(Level::Warn, &self.warn)?
+            .extend_from_specs(Level::Deny, &self.deny)?;
+        Ok(())
+    }
+}
+
+/// Shell-style glob supporting `*` and `?`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p = pattern.as_bytes();
+    let t = text.as_bytes();
+    let (mut pi, mut ti) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == b'?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == b'*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    while pi < p.len() && p[pi] == b'*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
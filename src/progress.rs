@@ -0,0 +1,18 @@
+/// Receives progress updates from long-running workflows.
+///
+/// Implementations must be shareable across threads because workflows may
+/// report from worker threads.
+pub trait Progress: Sync {
+    /// A new workflow stage started.
+    fn stage(&self, name: &str);
+
+    /// Additional detail about the current stage.
+    fn detail(&self, _message: &str) {}
+}
+
+/// Discards all progress updates.
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn stage(&self, _name: &str) {}
+}
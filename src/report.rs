@@ -1,5 +1,9 @@
+use crate::progress::Progress;
+use crate::wheel::{
+    BatchResult, EnvAudit, InstalledResult, RevendorResult, SplitResult, WheelOutcome, WheelPlan,
+    WheelWorkflowResult,
+};
 use crate::{AbiCheckResult, Severity};
-use anyhow::Result;
 use std::fmt;
 use std::io::Write;
 use std::str::FromStr;
@@ -88,8 +92,13 @@ pub fn print_text(result: &AbiCheckResult, verbose: bool, color_mode: ColorMode)
 
     let mut errors = 0usize;
     let mut warnings = 0usize;
+    let mut suppressed = 0usize;
 
     for diag in &result.diagnostics {
+        if diag.suppressed {
+            suppressed += 1;
+            continue;
+        }
         match diag.severity {
             Severity::Error => errors += 1,
             Severity::Warning => warnings += 1,
@@ -113,9 +122,10 @@ pub fn print_text(result: &AbiCheckResult, verbose: bool, color_mode: ColorMode)
                 .unwrap_or_default();
             let _ = writeln!(
                 stderr,
-                "{style}{prefix}{reset} {dim}({layer:?}){reset}{sym}: {msg}",
+                "{style}{prefix}{reset} {code} {dim}({layer:?}){reset}{sym}: {msg}",
                 style = style,
                 prefix = prefix,
+                code = diag.code.id,
                 reset = s.reset,
                 dim = s.layer,
                 layer = diag.layer,
@@ -127,8 +137,12 @@ pub fn print_text(result: &AbiCheckResult, verbose: bool, color_mode: ColorMode)
 
     let _ = writeln!(stderr);
 
-    if errors > 0 || warnings > 0 {
-        let _ = writeln!(stderr, "{} error(s), {} warning(s)", errors, warnings,);
+    if errors > 0 || warnings > 0 || suppressed > 0 {
+        let mut summary = format!("{} error(s), {} warning(s)", errors, warnings);
+        if suppressed > 0 {
+            summary.push_str(&format!(", {} suppressed", suppressed));
+        }
+        let _ = writeln!(stderr, "{}", summary);
     }
 
     let (verdict_str, verdict_style) = match result.verdict {
@@ -144,8 +158,426 @@ pub fn print_text(result: &AbiCheckResult, verbose: bool, color_mode: ColorMode)
     );
 }
 
-pub fn print_json(result: &AbiCheckResult) -> Result<()> {
+pub fn print_json(result: &AbiCheckResult) -> std::io::Result<()> {
     let json = serde_json::to_string_pretty(result)?;
     println!("{}", json);
     Ok(())
 }
+
+pub fn print_wheel_text(result: &WheelWorkflowResult, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (green, red, yellow, reset) = if color {
+        ("\x1b[1;32m", "\x1b[1;31m", "\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    for warning in &result.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+    for failure in &result.failures {
+        eprintln!("{red}FAIL{reset}: {failure}");
+    }
+
+    eprintln!();
+    eprintln!("Wheel: {}", result.input_wheel);
+    if result.rolled_back {
+        eprintln!("Output: {} (not written, rolled back)", result.output_wheel);
+    } else {
+        eprintln!("Output: {}", result.output_wheel);
+    }
+    eprintln!("Matched pairs: {}", result.summary.matched_pairs);
+    eprintln!("Checked extensions: {}", result.summary.checked_extensions);
+    eprintln!("Patched extensions: {}", result.summary.patched_extensions);
+    if result.summary.held_back_extensions > 0 {
+        eprintln!(
+            "Held-back extensions: {}",
+            result.summary.held_back_extensions
+        );
+    }
+    eprintln!(
+        "Removed bundled libs: {}",
+        result.summary.removed_bundled_libs
+    );
+    if result.summary.pruned_bundled_libs > 0 {
+        eprintln!(
+            "Pruned unreachable libs: {} ({} saved)",
+            result.summary.pruned_bundled_libs,
+            format_bytes(result.summary.pruned_bytes)
+        );
+    }
+    eprintln!(
+        "Skipped/incompatible checks: {}",
+        result.summary.skipped_checks
+    );
+
+    if result.is_complete() {
+        eprintln!("{green}Result: COMPLETE{reset}");
+    } else {
+        eprintln!("{red}Result: INCOMPLETE{reset}");
+    }
+}
+
+/// One line per wheel, with wheels whose outcome differs from the rest of
+/// the batch highlighted.
+pub fn print_batch_text(result: &BatchResult, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (green, red, yellow, reset) = if color {
+        ("\x1b[1;32m", "\x1b[1;31m", "\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    eprintln!();
+    eprintln!(
+        "Common outcome: {}",
+        describe_outcome(&result.common_outcome)
+    );
+    for entry in &result.wheels {
+        let status = match &entry.result {
+            Some(wheel) if wheel.is_complete() => format!("{green}COMPLETE{reset}"),
+            Some(_) => format!("{red}INCOMPLETE{reset}"),
+            None => format!("{red}ERROR{reset}"),
+        };
+        eprintln!("{status} {}", entry.wheel);
+        if let Some(error) = &entry.error {
+            eprintln!("    {error}");
+        }
+        if entry.differs {
+            eprintln!(
+                "    {yellow}differs{reset}: {}",
+                describe_outcome(&entry.outcome)
+            );
+        }
+    }
+
+    if result.is_complete() {
+        eprintln!("{green}Result: COMPLETE{reset}");
+    } else {
+        eprintln!("{red}Result: INCOMPLETE{reset}");
+    }
+}
+
+fn describe_outcome(outcome: &WheelOutcome) -> String {
+    if outcome.error {
+        return "failed with an error".to_string();
+    }
+    let list = |names: &std::collections::BTreeSet<String>| {
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.iter().cloned().collect::<Vec<_>>().join(", ")
+        }
+    };
+    format!(
+        "unvendored {}; kept bundled for {}",
+        list(&outcome.unvendored),
+        list(&outcome.kept)
+    )
+}
+
+pub fn print_batch_json(result: &BatchResult) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(result)?);
+    Ok(())
+}
+
+/// Summary of a plan on stderr; the plan itself is JSON.
+pub fn print_plan_text(plan: &WheelPlan, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (green, red, yellow, reset) = if color {
+        ("\x1b[1;32m", "\x1b[1;31m", "\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    for warning in &plan.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+    for failure in &plan.failures {
+        eprintln!("{red}FAIL{reset}: {failure}");
+    }
+
+    eprintln!();
+    eprintln!("Wheel: {}", plan.wheel);
+    eprintln!("Matched pairs: {}", plan.pairs.len());
+    eprintln!("Checked extensions: {}", plan.checks.len());
+    eprintln!("Planned DT_NEEDED edits: {}", plan.edits.len());
+    eprintln!("Planned removals: {}", plan.remove.len());
+
+    if plan.is_complete() {
+        eprintln!("{green}Plan: COMPLETE{reset}");
+    } else {
+        eprintln!("{red}Plan: INCOMPLETE{reset}");
+    }
+}
+
+pub fn print_wheel_json(result: &WheelWorkflowResult) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(result)?);
+    Ok(())
+}
+
+pub fn print_installed_text(result: &InstalledResult, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (green, red, yellow, reset) = if color {
+        ("\x1b[1;32m", "\x1b[1;31m", "\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    for warning in &result.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+    for failure in &result.failures {
+        eprintln!("{red}FAIL{reset}: {failure}");
+    }
+
+    eprintln!();
+    eprintln!("Site-packages: {}", result.site_packages);
+    if result.rolled_back {
+        eprintln!(
+            "Distribution: {} (unchanged, rolled back)",
+            result.dist_info
+        );
+    } else {
+        eprintln!("Distribution: {}", result.dist_info);
+    }
+    eprintln!("Matched pairs: {}", result.summary.matched_pairs);
+    eprintln!("Checked extensions: {}", result.summary.checked_extensions);
+    eprintln!("Patched extensions: {}", result.summary.patched_extensions);
+    if result.summary.held_back_extensions > 0 {
+        eprintln!(
+            "Held-back extensions: {}",
+            result.summary.held_back_extensions
+        );
+    }
+    eprintln!(
+        "Removed bundled libs: {}",
+        result.summary.removed_bundled_libs
+    );
+    if result.summary.pruned_bundled_libs > 0 {
+        eprintln!(
+            "Pruned unreachable libs: {} ({} saved)",
+            result.summary.pruned_bundled_libs,
+            format_bytes(result.summary.pruned_bytes)
+        );
+    }
+    eprintln!(
+        "Skipped/incompatible checks: {}",
+        result.summary.skipped_checks
+    );
+
+    if result.is_complete() {
+        eprintln!("{green}Result: COMPLETE{reset}");
+    } else {
+        eprintln!("{red}Result: INCOMPLETE{reset}");
+    }
+}
+
+pub fn print_installed_json(result: &InstalledResult) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(result)?);
+    Ok(())
+}
+
+/// Vendored libraries grouped across distributions, duplicated groups
+/// highlighted, followed by one line per unvendored distribution.
+pub fn print_audit_text(audit: &EnvAudit, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (green, red, yellow, reset) = if color {
+        ("\x1b[1;32m", "\x1b[1;31m", "\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "", "", "")
+    };
+
+    eprintln!("Site-packages: {}", audit.site_packages);
+    for warning in &audit.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+    for group in &audit.groups {
+        let version = if group.version.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", group.version)
+        };
+        let copies = group.copies.len();
+        let marker = if copies > 1 {
+            format!("{yellow}{copies} copies{reset}")
+        } else {
+            "1 copy".to_string()
+        };
+        eprintln!(
+            "{}{version}: {marker}, {}",
+            group.stem,
+            format_bytes(group.total_bytes)
+        );
+        for copy in &group.copies {
+            eprintln!(
+                "    {} {} ({})",
+                copy.dist_info,
+                copy.path,
+                format_bytes(copy.size)
+            );
+        }
+    }
+    eprintln!();
+    eprintln!(
+        "Vendored libraries: {} in {} group(s), {}",
+        audit.groups.iter().map(|g| g.copies.len()).sum::<usize>(),
+        audit.groups.len(),
+        format_bytes(audit.total_bytes)
+    );
+    eprintln!(
+        "Duplicated: {} group(s), {}",
+        audit.duplicated().count(),
+        format_bytes(audit.duplicate_bytes)
+    );
+
+    if audit.distributions.is_empty() {
+        return;
+    }
+    for entry in &audit.distributions {
+        let status = match &entry.result {
+            Some(result) if result.is_complete() => format!("{green}COMPLETE{reset}"),
+            Some(_) => format!("{red}INCOMPLETE{reset}"),
+            None => format!("{red}ERROR{reset}"),
+        };
+        eprintln!("{status} {}", entry.dist_info);
+        if let Some(error) = &entry.error {
+            eprintln!("    {error}");
+        }
+        if let Some(result) = &entry.result {
+            for failure in &result.failures {
+                eprintln!("    {red}FAIL{reset}: {failure}");
+            }
+            if !result.removed_bundled_paths.is_empty() {
+                eprintln!("    removed {}", result.removed_bundled_paths.join(", "));
+            }
+        }
+    }
+    if audit.is_complete() {
+        eprintln!("{green}Result: COMPLETE{reset}");
+    } else {
+        eprintln!("{red}Result: INCOMPLETE{reset}");
+    }
+}
+
+pub fn print_audit_json(audit: &EnvAudit) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(audit)?);
+    Ok(())
+}
+
+pub fn print_split_text(result: &SplitResult, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (yellow, reset) = if color {
+        ("\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+
+    for warning in &result.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+
+    eprintln!();
+    eprintln!("Wheel: {}", result.input_wheel);
+    eprintln!("Output: {}", result.output_wheel);
+    eprintln!(
+        "Library wheel: {} ({})",
+        result.libs_wheel, result.libs_distribution
+    );
+    eprintln!("Moved libs: {}", result.moved.len());
+    for name in &result.moved {
+        eprintln!("    {name}");
+    }
+    eprintln!("Relinked objects: {}", result.relinked.len());
+}
+
+pub fn print_split_json(result: &SplitResult) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(result)?);
+    Ok(())
+}
+
+pub fn print_revendor_text(result: &RevendorResult, color_mode: ColorMode) {
+    let color = use_color(color_mode);
+    let (yellow, reset) = if color {
+        ("\x1b[1;33m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+
+    for warning in &result.warnings {
+        eprintln!("{yellow}WARN{reset}: {warning}");
+    }
+
+    eprintln!();
+    eprintln!("Wheel: {}", result.input_wheel);
+    eprintln!("Output: {}", result.output_wheel);
+    eprintln!(
+        "Source: {}",
+        if result.from_provenance {
+            "unrepair.json"
+        } else {
+            "Requires-External"
+        }
+    );
+    eprintln!("Vendored libs: {}", result.libraries.len());
+    for lib in &result.libraries {
+        eprintln!(
+            "    {} -> {} ({} object(s))",
+            lib.system_path,
+            lib.path,
+            lib.extensions.len()
+        );
+    }
+}
+
+pub fn print_revendor_json(result: &RevendorResult) -> std::io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(result)?);
+    Ok(())
+}
+
+/// Size in B, KiB, MiB or GiB with one decimal.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// [`Progress`] implementation used by the CLI: stages go to stderr, details
+/// only when verbose.
+pub struct StderrProgress {
+    color_mode: ColorMode,
+    verbose: bool,
+}
+
+impl StderrProgress {
+    pub fn new(color_mode: ColorMode, verbose: bool) -> Self {
+        Self {
+            color_mode,
+            verbose,
+        }
+    }
+}
+
+impl Progress for StderrProgress {
+    fn stage(&self, name: &str) {
+        if use_color(self.color_mode) {
+            eprintln!("\x1b[1;34m==>\x1b[0m {}", name);
+        } else {
+            eprintln!("==> {}", name);
+        }
+    }
+
+    fn detail(&self, message: &str) {
+        if self.verbose {
+            eprintln!("{}", message);
+        }
+    }
+}
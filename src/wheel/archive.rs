@@ -0,0 +1,595 @@
+use super::model::{Scheme, WheelLayout};
+use super::record::{
+    hasher_for, parse_record, quote_record_path, record_line, HashingWriter, RecordLine,
+};
+use crate::elf::header::{has_elf_magic, ELF_MAGIC};
+use crate::error::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::read::ZipArchive;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::DateTime;
+
+/// Limits checked before anything is extracted, against zip bombs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// Maximum number of entries in the archive.
+    pub max_entries: usize,
+    /// Maximum decompressed size of a single entry, in bytes.
+    pub max_entry_size: u64,
+    /// Maximum decompressed size of all entries together, in bytes.
+    pub max_total_size: u64,
+    /// Maximum compression ratio for entries larger than 1 MiB.
+    pub max_ratio: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 100_000,
+            max_entry_size: 16 << 30,
+            max_total_size: 64 << 30,
+            max_ratio: 1_000,
+        }
+    }
+}
+
+// Small text files legitimately compress very well.
+const RATIO_EXEMPT_SIZE: u64 = 1 << 20;
+
+/// Changes to the input wheel, keyed by input entry name.
+#[derive(Default)]
+pub(crate) struct Changes {
+    /// Entries rewritten on disk under the extraction root.
+    pub(crate) modified: HashSet<String>,
+    /// Entries replaced with new contents.
+    pub(crate) generated: HashMap<String, Vec<u8>>,
+    /// Entries left out of the output.
+    pub(crate) removed: HashSet<String>,
+    /// New entries, keyed by output name.
+    pub(crate) added: Vec<(String, Vec<u8>)>,
+}
+
+/// An input wheel; only ELF objects are extracted, the rest is copied raw.
+pub(crate) struct WheelArchive {
+    path: PathBuf,
+    archive: ZipArchive<File>,
+    layout: WheelLayout,
+    record_name: String,
+    limits: ArchiveLimits,
+    /// Entries whose RECORD line failed verification.
+    stale: HashSet<String>,
+}
+
+impl WheelArchive {
+    pub(crate) fn open(path: &Path, limits: ArchiveLimits) -> Result<Self> {
+        let file = File::open(path).map_err(Error::io(path))?;
+        let mut archive = ZipArchive::new(file).map_err(Error::zip(path))?;
+        validate(&mut archive, path, &limits)?;
+
+        let names = archive
+            .file_names()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        let layout = WheelLayout::from_entries(path, names.iter().map(String::as_str), |name| {
+            let mut text = String::new();
+            match archive.by_name(name) {
+                Ok(mut entry) => entry.read_to_string(&mut text).map_err(Error::io(path))?,
+                Err(ZipError::FileNotFound) => {
+                    return Err(Error::wheel(path, format!("wheel has no {name}")))
+                }
+                Err(err) => return Err(Error::zip(path)(err)),
+            };
+            Ok(text)
+        })?;
+        let record_name = layout.record_name();
+        if !names.contains(&record_name) {
+            return Err(Error::wheel(
+                path,
+                format!("wheel has no {record_name}, cannot repackage safely"),
+            ));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            archive,
+            layout,
+            record_name,
+            limits,
+            stale: HashSet::new(),
+        })
+    }
+
+    pub(crate) fn layout(&self) -> &WheelLayout {
+        &self.layout
+    }
+
+    /// Extract ELF objects, found by magic bytes, keeping archive paths.
+    pub(crate) fn extract_elf_objects(&mut self, out_root: &Path) -> Result<()> {
+        for i in 0..self.archive.len() {
+            let mut entry = self.archive.by_index(i).map_err(Error::zip(&self.path))?;
+            let Some(rel) = entry.enclosed_name().map(Path::to_path_buf) else {
+                continue;
+            };
+            if entry.is_dir() {
+                continue;
+            }
+            let mut magic = Vec::with_capacity(ELF_MAGIC.len());
+            (&mut entry)
+                .take(ELF_MAGIC.len() as u64)
+                .read_to_end(&mut magic)
+                .map_err(Error::io(&self.path))?;
+            if !has_elf_magic(&magic) {
+                continue;
+            }
+            let name = entry.name().to_owned();
+            let outpath = out_root.join(rel);
+            if let Some(parent) = outpath.parent() {
+                fs::create_dir_all(parent).map_err(Error::io(parent))?;
+            }
+            let mut outfile = File::create(&outpath).map_err(Error::io(&outpath))?;
+            copy_capped(
+                magic.as_slice().chain(entry),
+                &mut outfile,
+                self.limits.max_entry_size,
+                &self.path,
+                &name,
+                &outpath,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the output wheel laid out as `to`, copying unchanged entries raw.
+    // Rewritten entries keep the method, permissions and timestamp of the
+    // entry they replace, so identical inputs give identical output bytes.
+    pub(crate) fn rewrite(
+        &mut self,
+        output: &Path,
+        root: &Path,
+        changes: &Changes,
+        to: &WheelLayout,
+        mtime: Option<DateTime>,
+    ) -> Result<()> {
+        replace_atomically(output, |partial| {
+            self.write_wheel(partial, root, changes, to, mtime)
+        })
+    }
+
+    fn write_wheel(
+        &mut self,
+        output: &Path,
+        root: &Path,
+        changes: &Changes,
+        to: &WheelLayout,
+        mtime: Option<DateTime>,
+    ) -> Result<()> {
+        let recorded = self.read_record()?;
+
+        let mut order = Vec::with_capacity(self.archive.len());
+        for i in 0..self.archive.len() {
+            let entry = self
+                .archive
+                .by_index_raw(i)
+                .map_err(Error::zip(&self.path))?;
+            order.push((i, entry.name().to_owned(), entry.is_dir()));
+        }
+        order.sort_by_key(|(_, name, _)| {
+            let is_record = *name == self.record_name;
+            let is_metadata = matches!(self.layout.locate(name), Some((Scheme::Metadata, _)));
+            (is_record || is_metadata, is_record)
+        });
+
+        let file = File::create(output).map_err(Error::io(output))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let mut record = Vec::new();
+
+        for (i, name, is_dir) in order {
+            if changes.removed.contains(&name) || self.layout.is_signature(&name) {
+                continue;
+            }
+            let out_name = self.layout.rename_entry(to, &name);
+
+            if name == self.record_name {
+                for (added_name, contents) in &changes.added {
+                    let options = self.entry_options(i, contents.len() as u64, mtime)?;
+                    zip.start_file(added_name.as_str(), options)
+                        .map_err(Error::zip(output))?;
+                    let mut hashing = HashingWriter::new(&mut zip);
+                    hashing.write_all(contents).map_err(Error::io(output))?;
+                    record.push(record_line(added_name, &hashing.finish()));
+                }
+                record.push(format!("{},,", quote_record_path(&out_name)));
+                let contents = record
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect::<String>();
+                let options = self.entry_options(i, contents.len() as u64, mtime)?;
+                zip.start_file(out_name.as_str(), options)
+                    .map_err(Error::zip(output))?;
+                zip.write_all(contents.as_bytes())
+                    .map_err(Error::io(output))?;
+                continue;
+            }
+
+            if let Some(contents) = changes.generated.get(&name) {
+                let options = self.entry_options(i, contents.len() as u64, mtime)?;
+                zip.start_file(out_name.as_str(), options)
+                    .map_err(Error::zip(output))?;
+                let mut hashing = HashingWriter::new(&mut zip);
+                hashing.write_all(contents).map_err(Error::io(output))?;
+                record.push(record_line(&out_name, &hashing.finish()));
+                continue;
+            }
+
+            if changes.modified.contains(&name) {
+                let src = root.join(&name);
+                let size = fs::metadata(&src).map_err(Error::io(&src))?.len();
+                let options = self.entry_options(i, size, mtime)?;
+                zip.start_file(out_name.as_str(), options)
+                    .map_err(Error::zip(output))?;
+                let mut f = File::open(&src).map_err(Error::io(&src))?;
+                let mut hashing = HashingWriter::new(&mut zip);
+                io::copy(&mut f, &mut hashing).map_err(Error::io(output))?;
+                record.push(record_line(&out_name, &hashing.finish()));
+                continue;
+            }
+
+            let entry = self
+                .archive
+                .by_index_raw(i)
+                .map_err(Error::zip(&self.path))?;
+            if out_name == name {
+                zip.raw_copy_file(entry).map_err(Error::zip(output))?;
+            } else {
+                zip.raw_copy_file_rename(entry, out_name.as_str())
+                    .map_err(Error::zip(output))?;
+            }
+            if is_dir {
+                continue;
+            }
+            let carried = recorded
+                .get(&name)
+                .filter(|line| line.is_complete() && !self.stale.contains(&name));
+            match carried {
+                Some(line) => record.push(format!(
+                    "{},{},{}",
+                    quote_record_path(&out_name),
+                    line.hash,
+                    line.size
+                )),
+                None => {
+                    // Not listed in the input RECORD: hash the entry's
+                    // decompressed bytes without keeping them around.
+                    let entry = self.archive.by_index(i).map_err(Error::zip(&self.path))?;
+                    let mut hashing = HashingWriter::new(io::sink());
+                    copy_capped(
+                        entry,
+                        &mut hashing,
+                        self.limits.max_entry_size,
+                        &self.path,
+                        &name,
+                        &self.path,
+                    )?;
+                    record.push(record_line(&out_name, &hashing.finish()));
+                }
+            }
+        }
+
+        zip.finish().map_err(Error::zip(output))?;
+        Ok(())
+    }
+
+    /// Whether the input has an entry called `name`.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.archive.file_names().any(|entry| entry == name)
+    }
+
+    /// Contents of a text entry such as `METADATA`.
+    pub(crate) fn read_text(&mut self, name: &str) -> Result<String> {
+        let mut text = String::new();
+        self.archive
+            .by_name(name)
+            .map_err(Error::zip(&self.path))?
+            .read_to_string(&mut text)
+            .map_err(Error::io(&self.path))?;
+        Ok(text)
+    }
+
+    /// Options for rewriting entry `index` with `size` bytes of new content.
+    fn entry_options(
+        &mut self,
+        index: usize,
+        size: u64,
+        mtime: Option<DateTime>,
+    ) -> Result<FileOptions> {
+        let entry = self
+            .archive
+            .by_index_raw(index)
+            .map_err(Error::zip(&self.path))?;
+        let mut options = FileOptions::default()
+            .compression_method(entry.compression())
+            .last_modified_time(mtime.unwrap_or_else(|| entry.last_modified()))
+            .large_file(size >= u64::from(u32::MAX));
+        if let Some(mode) = entry.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+        Ok(options)
+    }
+
+    /// Signature files over RECORD, which rewriting invalidates.
+    pub(crate) fn signatures(&self) -> Vec<String> {
+        let mut names = self
+            .archive
+            .file_names()
+            .filter(|name| self.layout.is_signature(name))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// One message per entry that disagrees with the input RECORD.
+    pub(crate) fn verify_record(&mut self) -> Result<Vec<String>> {
+        let recorded = self.read_record()?;
+        let mut problems = Vec::new();
+        let mut present = HashSet::new();
+
+        for i in 0..self.archive.len() {
+            let mut entry = self.archive.by_index(i).map_err(Error::zip(&self.path))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_owned();
+            present.insert(name.clone());
+            if name == self.record_name || self.layout.is_signature(&name) {
+                continue;
+            }
+
+            let Some(line) = recorded.get(&name) else {
+                problems.push(format!("{name}: not listed in RECORD"));
+                continue;
+            };
+            let Some((algorithm, expected)) = line.hash.split_once('=') else {
+                problems.push(format!("{name}: RECORD has no hash"));
+                self.stale.insert(name);
+                continue;
+            };
+            let Some(hasher) = hasher_for(algorithm) else {
+                problems.push(format!("{name}: unsupported hash algorithm '{algorithm}'"));
+                self.stale.insert(name);
+                continue;
+            };
+
+            let mut hashing = HashingWriter::with_hasher(io::sink(), hasher);
+            copy_capped(
+                &mut entry,
+                &mut hashing,
+                self.limits.max_entry_size,
+                &self.path,
+                &name,
+                &self.path,
+            )?;
+            let (actual, len) = hashing.finish();
+            if actual != expected {
+                problems.push(format!("{name}: {algorithm} hash does not match RECORD"));
+                self.stale.insert(name);
+            } else if line.size.parse::<u64>().ok() != Some(len) {
+                problems.push(format!(
+                    "{name}: size {len} does not match RECORD size '{}'",
+                    line.size
+                ));
+                self.stale.insert(name);
+            }
+        }
+
+        let mut missing = recorded
+            .keys()
+            .filter(|name| !present.contains(*name) && !name.ends_with('/'))
+            .map(|name| format!("{name}: listed in RECORD but missing from the wheel"))
+            .collect::<Vec<_>>();
+        missing.sort();
+        problems.extend(missing);
+        Ok(problems)
+    }
+
+    /// Lines of the input RECORD keyed by path.
+    fn read_record(&mut self) -> Result<HashMap<String, RecordLine>> {
+        let record_name = self.record_name.clone();
+        let text = self.read_text(&record_name)?;
+
+        Ok(parse_record(&text).into_iter().collect())
+    }
+}
+
+/// Copy entry `name` of `wheel` to `out`, failing past `limit` decompressed
+/// bytes. I/O errors are reported against `io_path`.
+fn copy_capped(
+    entry: impl Read,
+    out: &mut impl Write,
+    limit: u64,
+    wheel: &Path,
+    name: &str,
+    io_path: &Path,
+) -> Result<()> {
+    // Do not trust the declared size: stop one byte past the limit.
+    let written =
+        io::copy(&mut entry.take(limit.saturating_add(1)), out).map_err(Error::io(io_path))?;
+    if written > limit {
+        return Err(Error::UnsafeEntry {
+            path: wheel.to_path_buf(),
+            entry: name.to_string(),
+            reason: format!("decompressed size exceeds the limit of {}", limit),
+        });
+    }
+    Ok(())
+}
+
+/// Write `entries` in order, then a generated RECORD called `record_name`.
+pub(crate) fn write_new_wheel(
+    output: &Path,
+    entries: &[(String, Vec<u8>)],
+    record_name: &str,
+    mtime: Option<DateTime>,
+) -> Result<()> {
+    let options_for = |contents: &[u8]| {
+        let mode = if has_elf_magic(contents) {
+            0o755
+        } else {
+            0o644
+        };
+        FileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(mtime.unwrap_or_default())
+            .unix_permissions(mode)
+    };
+    replace_atomically(output, |partial| {
+        let file = File::create(partial).map_err(Error::io(partial))?;
+        let mut zip = zip::ZipWriter::new(file);
+        let mut record = Vec::new();
+        for (name, contents) in entries {
+            zip.start_file(name.as_str(), options_for(contents))
+                .map_err(Error::zip(partial))?;
+            let mut hashing = HashingWriter::new(&mut zip);
+            hashing.write_all(contents).map_err(Error::io(partial))?;
+            record.push(record_line(name, &hashing.finish()));
+        }
+        record.push(format!("{},,", quote_record_path(record_name)));
+        let contents = record
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        zip.start_file(record_name, options_for(contents.as_bytes()))
+            .map_err(Error::zip(partial))?;
+        zip.write_all(contents.as_bytes())
+            .map_err(Error::io(partial))?;
+        zip.finish().map_err(Error::zip(partial))?;
+        Ok(())
+    })
+}
+
+fn replace_atomically(output: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(Error::io(parent))?;
+    }
+    let mut partial = output.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    match write(&partial) {
+        Ok(()) => fs::rename(&partial, output).map_err(Error::io(output)),
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+// Declared sizes only; extraction enforces them again on the actual bytes.
+fn validate(archive: &mut ZipArchive<File>, path: &Path, limits: &ArchiveLimits) -> Result<()> {
+    if archive.len() > limits.max_entries {
+        return Err(Error::wheel(
+            path,
+            format!(
+                "archive has {} entries, more than the limit of {}",
+                archive.len(),
+                limits.max_entries
+            ),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut total = 0u64;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).map_err(Error::zip(path))?;
+        let name = entry.name().to_owned();
+        let unsafe_entry = |reason: String| Error::UnsafeEntry {
+            path: path.to_path_buf(),
+            entry: name.clone(),
+            reason,
+        };
+
+        if entry.enclosed_name().is_none() {
+            return Err(unsafe_entry(
+                "path is absolute or escapes the archive root".to_string(),
+            ));
+        }
+        if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+        {
+            return Err(unsafe_entry("symlink entries are not allowed".to_string()));
+        }
+        if !seen.insert(name.clone()) {
+            return Err(unsafe_entry("duplicate entry name".to_string()));
+        }
+        if entry.size() > limits.max_entry_size {
+            return Err(unsafe_entry(format!(
+                "decompressed size {} exceeds the limit of {}",
+                entry.size(),
+                limits.max_entry_size
+            )));
+        }
+        if entry.size() > RATIO_EXEMPT_SIZE
+            && entry.size() / entry.compressed_size().max(1) > limits.max_ratio
+        {
+            return Err(unsafe_entry(format!(
+                "compression ratio exceeds the limit of {}",
+                limits.max_ratio
+            )));
+        }
+        total = total.saturating_add(entry.size());
+        if total > limits.max_total_size {
+            return Err(Error::wheel(
+                path,
+                format!(
+                    "decompressed size exceeds the limit of {} bytes",
+                    limits.max_total_size
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Clamped to the range DOS time can represent (1980 to 2107).
+pub(crate) fn zip_time_from_epoch(secs: u64) -> DateTime {
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let rem = secs % 86_400;
+    if year < 1980 {
+        return DateTime::default();
+    }
+    let (year, month, day, rem) = if year > 2107 {
+        (2107, 12, 31, 86_399)
+    } else {
+        (year, month, day, rem)
+    };
+    DateTime::from_date_and_time(
+        year as u16,
+        month,
+        day,
+        (rem / 3600) as u8,
+        (rem % 3600 / 60) as u8,
+        (rem % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// Proleptic Gregorian date for a count of days since 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u8, u8) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month as u8, day as u8)
+}
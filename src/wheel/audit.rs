@@ -0,0 +1,263 @@
+use super::batch::error_chain;
+use super::discover::{classify_object, ObjectKind, SystemCandidate};
+use super::installed::{is_inside, unvendor_installed};
+use super::record::parse_record;
+use super::{discover_systems, InstalledResult, WheelBuilder};
+use crate::elf::soname::{soname_prefix_match, soname_version, unhashed_soname_stem};
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::parallel;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds vendored libraries in a `site-packages`. Created by [`crate::Unrepair::audit_env`].
+pub struct EnvAuditBuilder {
+    site_packages: PathBuf,
+    unvendor: bool,
+    options: WheelBuilder,
+}
+
+/// Result of [`EnvAuditBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct EnvAudit {
+    pub site_packages: String,
+    /// Vendored libraries grouped by SONAME stem and version, in name order.
+    pub groups: Vec<VendoredGroup>,
+    /// Size of every vendored library.
+    pub total_bytes: u64,
+    /// Size of all copies but the largest in groups with several copies.
+    pub duplicate_bytes: u64,
+    /// One entry per distribution that was unvendored, when requested.
+    pub distributions: Vec<DistributionEntry>,
+    /// Installed objects that could not be read and were left out.
+    pub warnings: Vec<String>,
+}
+
+/// Copies of one library vendored by one or more distributions.
+#[derive(Debug, Serialize)]
+pub struct VendoredGroup {
+    /// SONAME stem without the auditwheel hash, e.g. `libgfortran`.
+    pub stem: String,
+    /// SONAME version suffix, e.g. `5.0.0`; empty when there is none.
+    pub version: String,
+    pub copies: Vec<VendoredCopy>,
+    pub total_bytes: u64,
+}
+
+/// One vendored library file.
+#[derive(Debug, Serialize)]
+pub struct VendoredCopy {
+    /// `.dist-info` directory of the distribution that installed it.
+    pub dist_info: String,
+    /// Path relative to `site-packages`.
+    pub path: String,
+    pub soname: String,
+    pub size: u64,
+}
+
+/// Outcome of unvendoring one distribution.
+#[derive(Debug, Serialize)]
+pub struct DistributionEntry {
+    pub dist_info: String,
+    /// `None` when the run failed with `error`.
+    pub result: Option<InstalledResult>,
+    pub error: Option<String>,
+}
+
+impl EnvAudit {
+    /// Groups with more than one copy.
+    pub fn duplicated(&self) -> impl Iterator<Item = &VendoredGroup> {
+        self.groups.iter().filter(|group| group.copies.len() > 1)
+    }
+
+    /// True when every distribution that was unvendored is complete.
+    pub fn is_complete(&self) -> bool {
+        self.distributions.iter().all(|entry| {
+            entry
+                .result
+                .as_ref()
+                .is_some_and(InstalledResult::is_complete)
+        })
+    }
+}
+
+impl EnvAuditBuilder {
+    pub(crate) fn new(site_packages: PathBuf) -> Self {
+        Self {
+            site_packages,
+            unvendor: false,
+            options: WheelBuilder::default(),
+        }
+    }
+
+    /// Also unvendor the distributions that match (default: report only).
+    pub fn unvendor(mut self, unvendor: bool) -> Self {
+        self.unvendor = unvendor;
+        self
+    }
+
+    /// Settings for unvendoring, as for [`super::InstalledBuilder::options`].
+    pub fn options(mut self, options: WheelBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(self) -> Result<EnvAudit> {
+        run(self)
+    }
+}
+
+fn run(audit: EnvAuditBuilder) -> Result<EnvAudit> {
+    let site = &audit.site_packages;
+    let mut options = audit.options;
+    let store = SummaryStore::new(options.cache.as_ref());
+
+    options.progress.stage("Scanning installed distributions");
+    let (copies, warnings) = scan(site, &store)?;
+    let mut grouped = BTreeMap::<(String, String), Vec<VendoredCopy>>::new();
+    for copy in copies {
+        let stem = unhashed_soname_stem(&copy.soname).unwrap_or_else(|| copy.soname.clone());
+        grouped
+            .entry((stem, soname_version(&copy.soname)))
+            .or_default()
+            .push(copy);
+    }
+    let groups = grouped
+        .into_iter()
+        .map(|((stem, version), copies)| VendoredGroup {
+            stem,
+            version,
+            total_bytes: copies.iter().map(|copy| copy.size).sum(),
+            copies,
+        })
+        .collect::<Vec<_>>();
+    let total_bytes = groups.iter().map(|group| group.total_bytes).sum();
+    let duplicate_bytes = groups
+        .iter()
+        .filter(|group| group.copies.len() > 1)
+        .map(|group| {
+            let largest = group.copies.iter().map(|copy| copy.size).max();
+            group.total_bytes - largest.unwrap_or(0)
+        })
+        .sum();
+
+    let mut distributions = Vec::new();
+    if audit.unvendor {
+        options.progress.stage("Discovering system libraries");
+        let systems = discover_systems(&options, &store)?;
+        let targets = matching_distributions(&groups, &systems);
+        let jobs = options.jobs;
+        options.jobs = 1;
+        let results = parallel::map(&targets, jobs, |dist_info| {
+            unvendor_installed(&options, site, dist_info.clone(), &store, &systems)
+        });
+        distributions = targets
+            .into_iter()
+            .zip(results)
+            .map(|(dist_info, result)| match result {
+                Ok(result) => DistributionEntry {
+                    dist_info,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(err) => DistributionEntry {
+                    dist_info,
+                    result: None,
+                    error: Some(error_chain(&err)),
+                },
+            })
+            .collect();
+    }
+
+    Ok(EnvAudit {
+        site_packages: site.display().to_string(),
+        groups,
+        total_bytes,
+        duplicate_bytes,
+        distributions,
+        warnings,
+    })
+}
+
+fn scan(site: &Path, store: &SummaryStore<'_>) -> Result<(Vec<VendoredCopy>, Vec<String>)> {
+    let mut dist_infos = Vec::new();
+    for entry in fs::read_dir(site).map_err(Error::io(site))? {
+        let path = entry.map_err(Error::io(site))?.path();
+        let is_dist_info = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(".dist-info"));
+        if is_dist_info && path.join("RECORD").is_file() {
+            dist_infos.push(path);
+        }
+    }
+    dist_infos.sort();
+
+    let mut copies = Vec::new();
+    let mut warnings = Vec::new();
+    for dir in dist_infos {
+        let dist_info = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let record = dir.join("RECORD");
+        let text = fs::read_to_string(&record).map_err(Error::io(&record))?;
+        for (name, _) in parse_record(&text) {
+            let rel = Path::new(&name);
+            if !is_inside(rel) {
+                continue;
+            }
+            let path = site.join(rel);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            match classify_object(&name, &path, store) {
+                Ok(Some(ObjectKind::VendoredLibrary)) => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    warnings.push(format!(
+                        "{dist_info}: skipped {name}: {}",
+                        error_chain(&err)
+                    ));
+                    continue;
+                }
+            }
+            let soname = store
+                .get(&path)?
+                .soname
+                .clone()
+                .filter(|soname| !soname.is_empty())
+                .or_else(|| rel.file_name().and_then(|n| n.to_str()).map(String::from))
+                .unwrap_or_default();
+            copies.push(VendoredCopy {
+                dist_info: dist_info.clone(),
+                path: name,
+                soname,
+                size: metadata.len(),
+            });
+        }
+    }
+    Ok((copies, warnings))
+}
+
+fn matching_distributions(groups: &[VendoredGroup], systems: &[SystemCandidate]) -> Vec<String> {
+    let mut names = groups
+        .iter()
+        .flat_map(|group| &group.copies)
+        .filter(|copy| {
+            systems
+                .iter()
+                .any(|system| soname_prefix_match(&copy.soname, &system.stem))
+        })
+        .map(|copy| copy.dist_info.clone())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
@@ -0,0 +1,260 @@
+use super::archive::WheelArchive;
+use super::{
+    default_output_wheel, discover_systems, Session, WheelBuilder, WheelWorkflowResult,
+    DEFAULT_LOCAL_VERSION,
+};
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::parallel;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Runs the wheel workflow over many wheels. Created by [`crate::Unrepair::wheels`].
+pub struct BatchBuilder {
+    wheels: Vec<PathBuf>,
+    wheel_dirs: Vec<PathBuf>,
+    output_dir: Option<PathBuf>,
+    options: WheelBuilder,
+}
+
+/// What a wheel run achieved, comparable across builds of the same package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WheelOutcome {
+    /// System SONAMEs at least one extension was switched to.
+    pub unvendored: BTreeSet<String>,
+    /// System SONAMEs at least one extension could not be switched to.
+    pub kept: BTreeSet<String>,
+    /// The run failed with an error.
+    pub error: bool,
+}
+
+/// One wheel of a batch.
+#[derive(Debug, Serialize)]
+pub struct BatchEntry {
+    pub wheel: String,
+    /// `None` when the run failed with `error`.
+    pub result: Option<WheelWorkflowResult>,
+    pub error: Option<String>,
+    pub outcome: WheelOutcome,
+    /// The outcome differs from the most common one in the batch.
+    pub differs: bool,
+}
+
+/// Result of [`BatchBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub wheels: Vec<BatchEntry>,
+    /// The most common outcome; entries that differ from it are flagged.
+    pub common_outcome: WheelOutcome,
+}
+
+impl BatchResult {
+    /// True when every wheel ran and is complete.
+    pub fn is_complete(&self) -> bool {
+        self.wheels.iter().all(|entry| {
+            entry
+                .result
+                .as_ref()
+                .is_some_and(WheelWorkflowResult::is_complete)
+        })
+    }
+}
+
+impl BatchBuilder {
+    pub(crate) fn new(wheels: Vec<PathBuf>) -> Self {
+        Self {
+            wheels,
+            wheel_dirs: Vec::new(),
+            output_dir: None,
+            options: WheelBuilder::default(),
+        }
+    }
+
+    /// Also process every `*.whl` file directly inside `dir`.
+    pub fn wheels_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.wheel_dirs.push(dir.into());
+        self
+    }
+
+    /// Directory for the output wheels (default: next to each input).
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Settings for every wheel; the wheel and output paths are ignored.
+    pub fn options(mut self, options: WheelBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(self) -> Result<BatchResult> {
+        run(self)
+    }
+}
+
+fn run(batch: BatchBuilder) -> Result<BatchResult> {
+    let mut given = batch.wheels;
+    for dir in &batch.wheel_dirs {
+        given.extend(wheels_in(dir)?);
+    }
+    let mut seen = HashSet::new();
+    let mut wheels = Vec::new();
+    for wheel in given {
+        // Missing wheels are kept so that their entry reports the error.
+        if seen.insert(fs::canonicalize(&wheel).unwrap_or_else(|_| wheel.clone())) {
+            wheels.push(wheel);
+        }
+    }
+    if wheels.is_empty() {
+        return Err(Error::InvalidArgument("no wheels to process".to_string()));
+    }
+    check_output_paths(&wheels, &batch.options, batch.output_dir.as_deref())?;
+    if let Some(dir) = &batch.output_dir {
+        fs::create_dir_all(dir).map_err(Error::io(dir))?;
+    }
+
+    let mut options = batch.options;
+    let jobs = options.jobs;
+    let store = SummaryStore::new(options.cache.as_ref());
+    options.progress.stage(&format!(
+        "Discovering system libraries for {} wheel(s)",
+        wheels.len()
+    ));
+    let systems = discover_systems(&options, &store)?;
+    options.jobs = 1;
+
+    let results = parallel::map(&wheels, jobs, |wheel| {
+        let mut session = Session::open(&options, wheel, None)?;
+        if let Some(dir) = &batch.output_dir {
+            let name = session
+                .output_wheel
+                .file_name()
+                .expect("default output wheel has a file name");
+            session.output_wheel = dir.join(name);
+        }
+        session.run(&options, &store, &systems)
+    });
+
+    let mut entries = wheels
+        .iter()
+        .zip(results)
+        .map(|(wheel, result)| {
+            let (result, error) = match result {
+                Ok(result) => (Some(result), None),
+                Err(err) => (None, Some(error_chain(&err))),
+            };
+            BatchEntry {
+                wheel: wheel.display().to_string(),
+                outcome: result.as_ref().map(outcome).unwrap_or(WheelOutcome {
+                    error: true,
+                    ..WheelOutcome::default()
+                }),
+                result,
+                error,
+                differs: false,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let common_outcome = most_common(entries.iter().map(|entry| &entry.outcome));
+    for entry in &mut entries {
+        entry.differs = entry.outcome != common_outcome;
+    }
+    Ok(BatchResult {
+        wheels: entries,
+        common_outcome,
+    })
+}
+
+fn outcome(result: &WheelWorkflowResult) -> WheelOutcome {
+    let mut outcome = WheelOutcome::default();
+    for pair in &result.pairs {
+        if pair.patched_extensions > 0 {
+            outcome.unvendored.insert(pair.system_soname.clone());
+        }
+        if pair.incompatible_extensions > 0 || pair.held_back_extensions > 0 {
+            outcome.kept.insert(pair.system_soname.clone());
+        }
+    }
+    outcome
+}
+
+/// The most frequent outcome; ties go to the one seen first.
+fn most_common<'a>(outcomes: impl Iterator<Item = &'a WheelOutcome>) -> WheelOutcome {
+    let mut counts: Vec<(&WheelOutcome, usize)> = Vec::new();
+    for outcome in outcomes {
+        match counts.iter_mut().find(|(seen, _)| *seen == outcome) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((outcome, 1)),
+        }
+    }
+    let best = counts.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    counts
+        .into_iter()
+        .find(|(_, count)| *count == best)
+        .map(|(outcome, _)| outcome.clone())
+        .unwrap_or_default()
+}
+
+/// Fail if two wheels would be written to the same output path. Wheels that
+/// cannot be opened are left to report their own error.
+fn check_output_paths(
+    wheels: &[PathBuf],
+    options: &WheelBuilder,
+    output_dir: Option<&Path>,
+) -> Result<()> {
+    let mut outputs = HashMap::new();
+    for wheel in wheels {
+        let Ok(archive) = WheelArchive::open(wheel, options.limits) else {
+            continue;
+        };
+        let layout = archive
+            .layout()
+            .relabel(&options.retag, options.label_or(DEFAULT_LOCAL_VERSION));
+        let canonical = fs::canonicalize(wheel).unwrap_or_else(|_| wheel.clone());
+        let mut output = default_output_wheel(&canonical, &layout);
+        if let Some(dir) = output_dir {
+            let name = output
+                .file_name()
+                .expect("default output wheel has a file name");
+            output = dir.join(name);
+        }
+        if let Some(other) = outputs.insert(output.clone(), wheel) {
+            return Err(Error::InvalidArgument(format!(
+                "{} and {} would both be written to {}",
+                other.display(),
+                wheel.display(),
+                output.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `*.whl` files directly inside `dir`, sorted by name.
+fn wheels_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut wheels = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::io(dir))? {
+        let path = entry.map_err(Error::io(dir))?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "whl") {
+            wheels.push(path);
+        }
+    }
+    wheels.sort();
+    Ok(wheels)
+}
+
+/// The error and its sources on one line, as the CLI would print them.
+pub(super) fn error_chain(err: &Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
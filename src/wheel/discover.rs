@@ -0,0 +1,295 @@
+use super::model::WheelLayout;
+use super::provider::ProvidedLibrary;
+use crate::elf::header::{read_header, ElfType};
+use crate::elf::soname::{has_hash_suffix, soname_prefix_match, soname_stem};
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::parallel;
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub(crate) struct SystemCandidate {
+    /// For a library from a provider wheel, its extracted copy.
+    pub(crate) path: PathBuf,
+    pub(crate) soname: String,
+    pub(crate) stem: String,
+    pub(crate) provider: Option<ProvidedLibrary>,
+}
+
+impl SystemCandidate {
+    /// Where the library comes from, for reports.
+    pub(crate) fn describe(&self) -> String {
+        match &self.provider {
+            Some(provider) => provider.location(),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct BundledLib {
+    pub(crate) rel_path: PathBuf,
+    pub(crate) abs_path: PathBuf,
+    pub(crate) soname: String,
+}
+
+/// What an ELF object installed into `site-packages` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ObjectKind {
+    /// Exports `PyInit_<module>` for the module its file name is for.
+    Extension,
+    InternalLibrary,
+    /// In a `*.libs` directory, or named like `libz-a1b2c3d4.so.1`.
+    VendoredLibrary,
+    /// A program rather than a library.
+    Executable,
+}
+
+pub(crate) struct WheelObject {
+    pub(crate) rel_path: PathBuf,
+    pub(crate) abs_path: PathBuf,
+    pub(crate) kind: ObjectKind,
+}
+
+/// ELF objects that install into `site-packages`, found by magic bytes.
+pub(crate) fn classify_objects(
+    root: &Path,
+    layout: &WheelLayout,
+    store: &SummaryStore<'_>,
+) -> Result<Vec<WheelObject>> {
+    let mut out = Vec::new();
+    for rel in collect_files(root, false)? {
+        let name = rel_string(&rel);
+        let Some((_, inner)) = layout
+            .locate(&name)
+            .filter(|(scheme, _)| scheme.is_importable())
+        else {
+            continue;
+        };
+        let abs = root.join(&rel);
+        let Some(kind) = classify_object(inner, &abs, store)? else {
+            continue;
+        };
+        out.push(WheelObject {
+            rel_path: rel,
+            abs_path: abs,
+            kind,
+        });
+    }
+    Ok(out)
+}
+
+pub(crate) fn classify_object(
+    inner: &str,
+    path: &Path,
+    store: &SummaryStore<'_>,
+) -> Result<Option<ObjectKind>> {
+    let Some(header) = read_header(path)? else {
+        return Ok(None);
+    };
+    let summary = match header.elf_type {
+        ElfType::Executable => return Ok(Some(ObjectKind::Executable)),
+        ElfType::SharedObject => store.get(path)?,
+        ElfType::Other => return Ok(None),
+    };
+    let file_name = inner.rsplit('/').next().unwrap_or(inner);
+    if let Some(module) = extension_module_name(file_name) {
+        if summary.exports.contains_key(&format!("PyInit_{module}")) {
+            return Ok(Some(ObjectKind::Extension));
+        }
+    }
+    // A PIE program is an ET_DYN object too. Libraries that can also be run,
+    // like libc, have a SONAME.
+    if header.has_interpreter && summary.soname.is_none() {
+        return Ok(Some(ObjectKind::Executable));
+    }
+    if is_vendored_path(inner) || has_hash_suffix(file_name) {
+        Ok(Some(ObjectKind::VendoredLibrary))
+    } else {
+        Ok(Some(ObjectKind::InternalLibrary))
+    }
+}
+
+pub(crate) fn is_loadable_object(path: &Path) -> Result<bool> {
+    Ok(read_header(path)?.is_some_and(|header| header.elf_type != ElfType::Other))
+}
+
+/// `_core` for `_core.so`, `_core.abi3.so` and `_core.cpython-312-x86_64-linux-gnu.so`.
+fn extension_module_name(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(".so")?;
+    let (module, tag) = stem.split_once('.').unwrap_or((stem, ""));
+    let is_abi_tag = tag.is_empty()
+        || tag == "abi3"
+        || ["cpython-", "pypy", "graalpy"]
+            .iter()
+            .any(|prefix| tag.starts_with(prefix));
+    (!module.is_empty() && is_abi_tag).then_some(module)
+}
+
+pub(crate) fn is_vendored_path(inner: &str) -> bool {
+    inner
+        .split('/')
+        .rev()
+        .skip(1)
+        .any(|dir| dir.ends_with(".libs"))
+}
+
+pub(crate) fn consumers(objects: &[WheelObject]) -> Vec<PathBuf> {
+    objects
+        .iter()
+        .filter(|object| object.kind != ObjectKind::VendoredLibrary)
+        .map(|object| object.abs_path.clone())
+        .collect()
+}
+
+pub(crate) fn discover_bundled_libs(
+    root: &Path,
+    layout: &WheelLayout,
+    store: &SummaryStore<'_>,
+) -> Result<Vec<BundledLib>> {
+    bundled_libs(&classify_objects(root, layout, store)?, store)
+}
+
+pub(crate) fn bundled_libs(
+    objects: &[WheelObject],
+    store: &SummaryStore<'_>,
+) -> Result<Vec<BundledLib>> {
+    let mut out = Vec::new();
+    for object in objects {
+        if object.kind != ObjectKind::VendoredLibrary {
+            continue;
+        }
+        let son = store
+            .get(&object.abs_path)?
+            .soname
+            .clone()
+            .filter(|s| !s.is_empty())
+            .or_else(|| {
+                object
+                    .rel_path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .map(ToOwned::to_owned)
+            })
+            .unwrap_or_default();
+
+        out.push(BundledLib {
+            rel_path: object.rel_path.clone(),
+            abs_path: object.abs_path.clone(),
+            soname: son,
+        });
+    }
+    out.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(out)
+}
+
+// Libraries without a SONAME are skipped.
+pub(crate) fn discover_system_candidates(
+    system_libs: &[PathBuf],
+    system_lib_dirs: &[PathBuf],
+    provided: Vec<(PathBuf, ProvidedLibrary)>,
+    store: &SummaryStore<'_>,
+    jobs: usize,
+) -> Result<Vec<SystemCandidate>> {
+    let mut paths = system_libs.iter().cloned().collect::<BTreeSet<_>>();
+
+    for dir in system_lib_dirs {
+        for entry in WalkDir::new(dir).follow_links(true) {
+            let entry = entry.map_err(|e| Error::walk(dir, e))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if is_shared_object_name(entry.path().file_name()) {
+                paths.insert(entry.path().to_path_buf());
+            }
+        }
+    }
+
+    let paths = paths
+        .into_iter()
+        .map(|path| (path, None))
+        .chain(
+            provided
+                .into_iter()
+                .map(|(path, provider)| (path, Some(provider))),
+        )
+        .collect::<Vec<_>>();
+    let sonames = parallel::map(&paths, jobs, |(path, _)| {
+        store.get(path).map(|summary| summary.soname.clone())
+    });
+
+    let mut out = Vec::new();
+    for ((path, provider), son) in paths.into_iter().zip(sonames) {
+        let Some(soname_value) = son?.filter(|s| !s.is_empty()) else {
+            continue;
+        };
+        let Some(stem) = soname_stem(&soname_value) else {
+            continue;
+        };
+
+        out.push(SystemCandidate {
+            path,
+            soname: soname_value,
+            stem,
+            provider,
+        });
+    }
+    Ok(out)
+}
+
+pub(crate) fn build_mappings<'a>(
+    bundled: &'a [BundledLib],
+    systems: &'a [SystemCandidate],
+) -> std::result::Result<Vec<(&'a BundledLib, &'a SystemCandidate)>, String> {
+    let mut assigned_bundled = HashSet::<String>::new();
+    let mut out = Vec::new();
+
+    for sys in systems {
+        let mut matches = bundled
+            .iter()
+            .filter(|bun| soname_prefix_match(&bun.soname, &sys.stem))
+            .collect::<Vec<_>>();
+
+        if matches.len() > 1 {
+            let mut names = matches.iter().map(|m| m.soname.clone()).collect::<Vec<_>>();
+            names.sort();
+            return Err(format!(
+                "ambiguous mapping for system {} (SONAME {}): matched bundled {:?}",
+                sys.describe(),
+                sys.soname,
+                names
+            ));
+        }
+
+        if let Some(bun) = matches.pop() {
+            if assigned_bundled.insert(rel_string(&bun.rel_path)) {
+                out.push((bun, sys));
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub(crate) fn collect_files(root: &Path, follow_links: bool) -> Result<Vec<PathBuf>> {
+    let mut files = WalkDir::new(root)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.path().strip_prefix(root).ok().map(Path::to_path_buf))
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn is_shared_object_name(name: Option<&OsStr>) -> bool {
+    name.and_then(OsStr::to_str)
+        .map(|n| n.ends_with(".so") || n.contains(".so."))
+        .unwrap_or(false)
+}
+
+pub(crate) fn rel_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
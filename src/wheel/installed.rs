@@ -0,0 +1,351 @@
+use super::discover::{is_shared_object_name, rel_string, SystemCandidate};
+use super::model::canonical;
+use super::record::{hasher_for, parse_record, record_line, HashingWriter};
+use super::{
+    analyze, build_provenance, create_workdir, discover_systems, outcome_is_complete,
+    MappingExecution, PairResult, WheelBuilder, WheelLayout, WheelSummary, PROVENANCE_FILE,
+};
+use crate::elf::header::is_elf_file;
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Unvendors an installed distribution in place. Created by [`crate::Unrepair::installed`].
+pub struct InstalledBuilder {
+    site_packages: PathBuf,
+    distribution: String,
+    options: WheelBuilder,
+}
+
+/// Result of [`InstalledBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct InstalledResult {
+    pub site_packages: String,
+    /// Name of the distribution's `.dist-info` directory.
+    pub dist_info: String,
+    pub strict: bool,
+    pub transactional: bool,
+    /// Set when a transactional run is rolled back.
+    pub hard_failure: bool,
+    /// A transactional run found a failure, so nothing was changed.
+    pub rolled_back: bool,
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+    pub pairs: Vec<PairResult>,
+    pub removed_bundled_paths: Vec<String>,
+    pub summary: WheelSummary,
+}
+
+impl InstalledResult {
+    /// False on a hard failure, or in strict mode when any action failed.
+    pub fn is_complete(&self) -> bool {
+        outcome_is_complete(self.hard_failure, self.strict, &self.failures)
+    }
+}
+
+impl InstalledBuilder {
+    pub(crate) fn new(site_packages: PathBuf, distribution: String) -> Self {
+        Self {
+            site_packages,
+            distribution,
+            options: WheelBuilder::default(),
+        }
+    }
+
+    /// Settings for the run; wheel, output and labelling settings are ignored.
+    pub fn options(mut self, options: WheelBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(self) -> Result<InstalledResult> {
+        run(&self)
+    }
+}
+
+fn run(installed: &InstalledBuilder) -> Result<InstalledResult> {
+    let args = &installed.options;
+    let site = &installed.site_packages;
+    let dist_info = find_dist_info(site, &installed.distribution)?;
+    let store = SummaryStore::new(args.cache.as_ref());
+    let systems = discover_systems(args, &store)?;
+    unvendor_installed(args, site, dist_info, &store, &systems)
+}
+
+pub(super) fn unvendor_installed(
+    args: &WheelBuilder,
+    site: &Path,
+    dist_info: String,
+    store: &SummaryStore<'_>,
+    systems: &[SystemCandidate],
+) -> Result<InstalledResult> {
+    let dist_dir = site.join(&dist_info);
+    let fail = |reason: String| Error::installed(&dist_dir, reason);
+
+    args.progress.stage("Discovering installed files");
+    let wheel_file = dist_dir.join("WHEEL");
+    let wheel_text = fs::read_to_string(&wheel_file).map_err(Error::io(&wheel_file))?;
+    let layout = WheelLayout::installed(&dist_info, &wheel_text).map_err(fail)?;
+    let record_path = dist_dir.join("RECORD");
+    let record_text = fs::read_to_string(&record_path).map_err(Error::io(&record_path))?;
+
+    let tmp = create_workdir(args.workdir.as_deref())?;
+    let root = tmp.path().join("site-packages");
+    fs::create_dir_all(&root).map_err(Error::io(&root))?;
+    let mut warnings = Vec::new();
+    let problems = copy_elf_objects(site, &record_text, &root)?;
+    if !problems.is_empty() {
+        if !args.ignore_record_errors {
+            return Err(Error::RecordMismatch {
+                path: record_path,
+                problems,
+            });
+        }
+        warnings.extend(
+            problems
+                .into_iter()
+                .map(|problem| format!("RECORD: {problem}")),
+        );
+    }
+
+    let (exec, removed) = analyze(&root, &layout, args, store, systems, &fail)?;
+    if exec.rolled_back {
+        warnings.push(format!(
+            "Rolled back: {} failure(s), no extension was patched and {} was left unchanged",
+            exec.failures.len(),
+            dist_dir.display()
+        ));
+    } else {
+        args.progress.stage("Updating installed files");
+        install(site, &root, &layout, &record_text, &exec, &removed)?;
+    }
+
+    warnings.extend(exec.warnings);
+    Ok(InstalledResult {
+        site_packages: site.display().to_string(),
+        dist_info,
+        strict: args.strict,
+        transactional: args.transactional,
+        hard_failure: exec.rolled_back,
+        rolled_back: exec.rolled_back,
+        failures: exec.failures,
+        warnings,
+        summary: WheelSummary {
+            matched_pairs: exec.pairs.len(),
+            checked_extensions: exec.checked_extensions,
+            patched_extensions: exec.patched_extensions,
+            held_back_extensions: exec.held_back_extensions,
+            removed_bundled_libs: removed.len(),
+            skipped_checks: exec.skipped_checks,
+            pruned_bundled_libs: exec.pruned.len(),
+            pruned_bytes: exec.pruned_bytes,
+        },
+        pairs: exec.pairs,
+        removed_bundled_paths: removed,
+    })
+}
+
+pub(super) fn find_dist_info(site: &Path, distribution: &str) -> Result<String> {
+    let wanted = canonical(distribution);
+    let mut found = Vec::new();
+    for entry in fs::read_dir(site).map_err(Error::io(site))? {
+        let entry = entry.map_err(Error::io(site))?;
+        let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+            continue;
+        };
+        let matches = name
+            .strip_suffix(".dist-info")
+            .and_then(|stem| stem.split_once('-'))
+            .is_some_and(|(dist, _)| canonical(dist) == wanted);
+        if matches && entry.path().is_dir() {
+            found.push(name);
+        }
+    }
+    found.sort();
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        0 => Err(Error::InvalidArgument(format!(
+            "no installed distribution named '{distribution}' in {}",
+            site.display()
+        ))),
+        _ => Err(Error::InvalidArgument(format!(
+            "several installed distributions named '{distribution}' in {}: {}",
+            site.display(),
+            found.join(", ")
+        ))),
+    }
+}
+
+/// Copy the ELF objects in RECORD to `root`, returning RECORD mismatches.
+fn copy_elf_objects(site: &Path, record_text: &str, root: &Path) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    for (name, line) in parse_record(record_text) {
+        let rel = Path::new(&name);
+        if !is_inside(rel) {
+            continue;
+        }
+        let source = site.join(rel);
+        if !source.is_file() {
+            if is_shared_object_name(rel.file_name()) {
+                problems.push(format!("{name}: listed in RECORD but not installed"));
+            }
+            continue;
+        }
+        if !is_elf_file(&source)? {
+            continue;
+        }
+        let target = root.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(Error::io(parent))?;
+        }
+
+        let Some((algorithm, expected)) = line.hash.split_once('=') else {
+            problems.push(format!("{name}: RECORD has no hash"));
+            fs::copy(&source, &target).map_err(Error::io(&source))?;
+            continue;
+        };
+        let Some(hasher) = hasher_for(algorithm) else {
+            problems.push(format!("{name}: unsupported hash algorithm '{algorithm}'"));
+            fs::copy(&source, &target).map_err(Error::io(&source))?;
+            continue;
+        };
+        let mut input = File::open(&source).map_err(Error::io(&source))?;
+        let output = File::create(&target).map_err(Error::io(&target))?;
+        let mut hashing = HashingWriter::with_hasher(output, hasher);
+        io::copy(&mut input, &mut hashing).map_err(Error::io(&source))?;
+        let (actual, len) = hashing.finish();
+        if actual != expected {
+            problems.push(format!("{name}: {algorithm} hash does not match RECORD"));
+        } else if line.size.parse::<u64>().ok() != Some(len) {
+            problems.push(format!(
+                "{name}: size {len} does not match RECORD size '{}'",
+                line.size
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+/// True for a relative path that stays inside the directory it is joined to.
+pub(super) fn is_inside(rel: &Path) -> bool {
+    rel.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+// Every file is replaced by a rename, and RECORD is written last.
+fn install(
+    site: &Path,
+    root: &Path,
+    layout: &WheelLayout,
+    record_text: &str,
+    exec: &MappingExecution,
+    removed: &[String],
+) -> Result<()> {
+    let mut changed = BTreeSet::new();
+    for patched in &exec.patched_files {
+        let Ok(rel) = patched.strip_prefix(root) else {
+            continue;
+        };
+        let target = site.join(rel);
+        let partial = partial_path(&target);
+        fs::copy(patched, &partial).map_err(Error::io(&partial))?;
+        // The scratch copy has default permissions; keep the installed mode,
+        // such as the executable bit of a program.
+        let permissions = fs::metadata(&target)
+            .map_err(Error::io(&target))?
+            .permissions();
+        fs::set_permissions(&partial, permissions).map_err(Error::io(&partial))?;
+        fs::rename(&partial, &target).map_err(Error::io(&target))?;
+        changed.insert(rel_string(rel));
+    }
+    for name in removed {
+        let path = site.join(name);
+        fs::remove_file(&path).map_err(Error::io(&path))?;
+        remove_empty_parents(site, &path);
+    }
+
+    let provenance = build_provenance(root, layout, layout, exec, removed)?;
+    let metadata_name = format!("{}/METADATA", layout.dist_info);
+    let metadata_path = site.join(&metadata_name);
+    let text = fs::read_to_string(&metadata_path).map_err(Error::io(&metadata_path))?;
+    let new_text = provenance.add_metadata_headers(&text);
+    if new_text != text {
+        write_replacing(&metadata_path, new_text.as_bytes())?;
+        changed.insert(metadata_name);
+    }
+    let provenance_name = format!("{}/{}", layout.dist_info, PROVENANCE_FILE);
+    write_replacing(&site.join(&provenance_name), &provenance.to_json())?;
+    changed.insert(provenance_name.clone());
+
+    let record_name = layout.record_name();
+    let removed = removed.iter().map(String::as_str).collect::<HashSet<_>>();
+    let mut hashes = BTreeMap::new();
+    for name in &changed {
+        hashes.insert(name.as_str(), hash_installed(&site.join(name))?);
+    }
+    let mut lines = Vec::new();
+    let mut listed = HashSet::new();
+    for line in record_text.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((name, _)) = parse_record(line).into_iter().next() else {
+            lines.push(line.to_string());
+            continue;
+        };
+        if removed.contains(name.as_str()) {
+            continue;
+        }
+        match hashes.get(name.as_str()) {
+            Some(hash) => lines.push(record_line(&name, hash)),
+            None => lines.push(line.to_string()),
+        }
+        listed.insert(name);
+    }
+    if !listed.contains(&provenance_name) {
+        // Keep RECORD's own line last, as installers write it.
+        let own = lines.iter().position(|line| {
+            parse_record(line)
+                .first()
+                .is_some_and(|(n, _)| *n == record_name)
+        });
+        let line = record_line(&provenance_name, &hashes[provenance_name.as_str()]);
+        match own {
+            Some(index) => lines.insert(index, line),
+            None => lines.push(line),
+        }
+    }
+    let mut record = lines.join("\n");
+    record.push('\n');
+    write_replacing(&site.join(&record_name), record.as_bytes())
+}
+
+fn hash_installed(path: &Path) -> Result<(String, u64)> {
+    let mut file = File::open(path).map_err(Error::io(path))?;
+    let mut hashing = HashingWriter::new(io::sink());
+    io::copy(&mut file, &mut hashing).map_err(Error::io(path))?;
+    Ok(hashing.finish())
+}
+
+fn write_replacing(path: &Path, contents: &[u8]) -> Result<()> {
+    let partial = partial_path(path);
+    fs::write(&partial, contents).map_err(Error::io(&partial))?;
+    fs::rename(&partial, path).map_err(Error::io(path))
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+/// Remove the now empty directories between `removed` and `site`.
+fn remove_empty_parents(site: &Path, removed: &Path) {
+    let mut dir = removed.parent();
+    while let Some(path) = dir {
+        if path == site || !path.starts_with(site) || fs::remove_dir(path).is_err() {
+            break;
+        }
+        dir = path.parent();
+    }
+}
@@ -0,0 +1,1343 @@
+mod archive;
+mod audit;
+mod batch;
+mod discover;
+mod installed;
+mod model;
+mod plan;
+mod provenance;
+mod provider;
+mod record;
+mod revendor;
+mod split;
+
+use crate::cache::{hash_file, AnalysisCache, SCRATCH_PREFIX};
+use crate::check::CheckRegistry;
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::parallel;
+use crate::progress::{NoProgress, Progress};
+use crate::{check_summaries, AbiCheckResult, Verdict};
+pub use archive::ArchiveLimits;
+use archive::{zip_time_from_epoch, Changes, WheelArchive};
+pub use audit::{DistributionEntry, EnvAudit, EnvAuditBuilder, VendoredCopy, VendoredGroup};
+pub use batch::{BatchBuilder, BatchEntry, BatchResult, WheelOutcome};
+use discover::{
+    build_mappings, bundled_libs, classify_objects, consumers, discover_bundled_libs,
+    discover_system_candidates, rel_string, BundledLib, ObjectKind, SystemCandidate,
+};
+pub use installed::{InstalledBuilder, InstalledResult};
+pub use model::{is_valid_local_version, Retag, Scheme, WheelLayout, WheelMetadata, WheelName};
+use model::{set_metadata_version, set_wheel_tags};
+pub use plan::{PlannedCheck, PlannedEdit, WheelPlan};
+pub use provenance::{NeededReplacement, Provenance, SystemLibrary, PROVENANCE_FILE};
+use provider::provided_libraries;
+pub use provider::{ProvidedLibrary, ProviderSource};
+pub use revendor::{RevendorBuilder, RevendorResult, RevendoredLibrary};
+use serde::{Deserialize, Serialize};
+pub use split::{SplitBuilder, SplitResult};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zip::DateTime;
+
+const DEFAULT_LOCAL_VERSION: &str = "unrepaired";
+
+/// Counters over the whole wheel run.
+#[derive(Debug, Serialize)]
+pub struct WheelSummary {
+    /// Bundled libraries matched to a system library.
+    pub matched_pairs: usize,
+    /// Extension/pair combinations that were checked.
+    pub checked_extensions: usize,
+    /// Extensions whose `DT_NEEDED` entry was rewritten.
+    pub patched_extensions: usize,
+    /// Compatible extensions left unpatched because of another failure.
+    pub held_back_extensions: usize,
+    /// Bundled libraries removed from the output wheel.
+    pub removed_bundled_libs: usize,
+    /// Checks that found the system library incompatible.
+    pub skipped_checks: usize,
+    /// Also counted in `removed_bundled_libs`.
+    pub pruned_bundled_libs: usize,
+    /// Size of the pruned libraries.
+    pub pruned_bytes: u64,
+}
+
+/// Outcome for one bundled library matched to one system library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairResult {
+    /// Path of the bundled library inside the wheel.
+    pub bundled_path: String,
+    pub bundled_soname: String,
+    pub system_path: String,
+    pub system_soname: String,
+    /// Extensions that depend on the bundled library and were checked.
+    pub checked_extensions: usize,
+    pub patched_extensions: usize,
+    /// Compatible extensions left unpatched because of another failure.
+    pub held_back_extensions: usize,
+    /// Extensions that do not depend on the bundled library.
+    pub skipped_extensions: usize,
+    pub incompatible_extensions: usize,
+}
+
+/// Result of [`WheelBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct WheelWorkflowResult {
+    pub input_wheel: String,
+    pub output_wheel: String,
+    pub strict: bool,
+    pub transactional: bool,
+    /// The workflow could not complete regardless of strictness.
+    pub hard_failure: bool,
+    /// Nothing was written because a transactional run found a failure.
+    pub rolled_back: bool,
+    /// Requested unvendoring actions that could not be performed.
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+    pub pairs: Vec<PairResult>,
+    /// Paths inside the wheel of bundled libraries that were removed.
+    pub removed_bundled_paths: Vec<String>,
+    pub summary: WheelSummary,
+}
+
+impl WheelWorkflowResult {
+    /// False on a hard failure, or in strict mode when any action failed.
+    pub fn is_complete(&self) -> bool {
+        outcome_is_complete(self.hard_failure, self.strict, &self.failures)
+    }
+}
+
+/// Completeness rule shared by every workflow result.
+pub(crate) fn outcome_is_complete(failed: bool, strict: bool, failures: &[String]) -> bool {
+    !failed && (!strict || failures.is_empty())
+}
+
+struct MappingExecution {
+    pairs: Vec<PairResult>,
+    warnings: Vec<String>,
+    failures: Vec<String>,
+    checked_extensions: usize,
+    patched_extensions: usize,
+    skipped_checks: usize,
+    patched_bundled_sonames: HashSet<String>,
+    /// Extension files rewritten on disk.
+    patched_files: BTreeSet<PathBuf>,
+    /// Every `DT_NEEDED` rewrite, in the order it was applied.
+    replacements: Vec<Replacement>,
+    held_back_extensions: usize,
+    rolled_back: bool,
+    /// Verdict for every checked extension, in check order.
+    outcomes: Vec<CheckOutcome>,
+    /// Bundled libraries removed as unreachable, and their total size.
+    pruned: Vec<String>,
+    pruned_bytes: u64,
+}
+
+struct CheckOutcome {
+    extension: PathBuf,
+    bundled_soname: String,
+    /// [`SystemCandidate::describe`] of the system library.
+    system_path: String,
+    verdict: Verdict,
+    patched: bool,
+}
+
+struct Replacement {
+    extension: PathBuf,
+    old_needed: String,
+    new_needed: String,
+    system_path: PathBuf,
+    provider: Option<ProvidedLibrary>,
+    /// RUNPATH entry added so the extension finds a provided library.
+    runpath: Option<String>,
+}
+
+/// Configures and runs the wheel workflow. Created by [`crate::Unrepair::wheel`].
+pub struct WheelBuilder {
+    wheel: PathBuf,
+    output_wheel: Option<PathBuf>,
+    system_libs: Vec<PathBuf>,
+    system_lib_dirs: Vec<PathBuf>,
+    providers: Vec<ProviderSource>,
+    strict: bool,
+    transactional: bool,
+    all_consumers: bool,
+    prune_unused: bool,
+    workdir: Option<PathBuf>,
+    checks: CheckRegistry,
+    cache: Option<AnalysisCache>,
+    jobs: usize,
+    source_date_epoch: Option<u64>,
+    limits: ArchiveLimits,
+    ignore_record_errors: bool,
+    retag: Retag,
+    // `None` until set: each command then uses its own default label.
+    local_version: Option<Option<String>>,
+    progress: Box<dyn Progress>,
+}
+
+/// Settings for [`BatchBuilder::options`]; the wheel path is unused.
+impl Default for WheelBuilder {
+    fn default() -> Self {
+        Self::new(PathBuf::new())
+    }
+}
+
+impl WheelBuilder {
+    pub(crate) fn new(wheel: PathBuf) -> Self {
+        Self {
+            wheel,
+            output_wheel: None,
+            system_libs: Vec::new(),
+            system_lib_dirs: Vec::new(),
+            providers: Vec::new(),
+            strict: true,
+            transactional: false,
+            all_consumers: false,
+            prune_unused: false,
+            workdir: None,
+            checks: CheckRegistry::builtin(),
+            cache: None,
+            jobs: parallel::default_jobs(),
+            source_date_epoch: None,
+            limits: ArchiveLimits::default(),
+            ignore_record_errors: false,
+            retag: Retag::Keep,
+            local_version: None,
+            progress: Box::new(NoProgress),
+        }
+    }
+
+    /// Output wheel path (default: [`default_output_wheel`]).
+    pub fn output_wheel(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_wheel = Some(path.into());
+        self
+    }
+
+    /// Add one system library candidate file.
+    pub fn system_lib(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_libs.push(path.into());
+        self
+    }
+
+    pub fn system_libs<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.system_libs.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a directory to scan recursively for system libraries.
+    pub fn system_lib_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_lib_dirs.push(path.into());
+        self
+    }
+
+    pub fn system_lib_dirs<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.system_lib_dirs
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Also offer the libraries a provider wheel installs as candidates.
+    pub fn provider_wheel(mut self, path: impl Into<PathBuf>) -> Self {
+        self.providers.push(ProviderSource::Wheel(path.into()));
+        self
+    }
+
+    /// Like [`WheelBuilder::provider_wheel`], for an installed distribution.
+    pub fn provider_installed(
+        mut self,
+        site_packages: impl Into<PathBuf>,
+        distribution: impl Into<String>,
+    ) -> Self {
+        self.providers.push(ProviderSource::Installed {
+            site_packages: site_packages.into(),
+            distribution: distribution.into(),
+        });
+        self
+    }
+
+    /// Any failed action makes the result incomplete (default: true).
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Write nothing unless every requested change passes its checks.
+    pub fn transactional(mut self, transactional: bool) -> Self {
+        self.transactional = transactional;
+        self
+    }
+
+    /// Only unvendor a library if every extension that uses it passes.
+    pub fn all_consumers(mut self, all_consumers: bool) -> Self {
+        self.all_consumers = all_consumers;
+        self
+    }
+
+    /// Also remove bundled libraries that nothing reaches, replaced or not.
+    pub fn prune_unused(mut self, prune_unused: bool) -> Self {
+        self.prune_unused = prune_unused;
+        self
+    }
+
+    /// Parent directory for temporary unpacked wheel data.
+    pub fn workdir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.workdir = Some(path.into());
+        self
+    }
+
+    pub fn checks(mut self, checks: CheckRegistry) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Reuse parsed system libraries and check results across runs.
+    pub fn cache(mut self, cache: AnalysisCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Worker threads used for parsing and checking (default: available CPUs).
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Timestamp for rewritten entries (default: `SOURCE_DATE_EPOCH`).
+    pub fn source_date_epoch(mut self, secs: u64) -> Self {
+        self.source_date_epoch = Some(secs);
+        self
+    }
+
+    /// Limits on the input archive's entry count and decompressed sizes.
+    pub fn limits(mut self, limits: ArchiveLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Warn instead of failing with [`Error::RecordMismatch`].
+    pub fn ignore_record_errors(mut self, ignore: bool) -> Self {
+        self.ignore_record_errors = ignore;
+        self
+    }
+
+    /// Change the platform tag in the output file name and `WHEEL`.
+    pub fn retag(mut self, retag: Retag) -> Self {
+        self.retag = retag;
+        self
+    }
+
+    /// PEP 440 local version label for the output (default: `unrepaired`).
+    pub fn local_version(mut self, label: impl Into<String>) -> Self {
+        self.local_version = Some(Some(label.into()));
+        self
+    }
+
+    /// Keep the input wheel's version unchanged.
+    pub fn no_local_version(mut self) -> Self {
+        self.local_version = Some(None);
+        self
+    }
+
+    /// The label set with [`Self::local_version`], or `default`.
+    fn label_or<'a>(&'a self, default: &'a str) -> Option<&'a str> {
+        match &self.local_version {
+            Some(label) => label.as_deref(),
+            None => Some(default),
+        }
+    }
+
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = Box::new(progress);
+        self
+    }
+
+    pub fn run(self) -> Result<WheelWorkflowResult> {
+        run(&self)
+    }
+
+    /// Describe the changes [`WheelBuilder::run`] would make.
+    pub fn plan(self) -> Result<WheelPlan> {
+        plan(&self)
+    }
+
+    /// Write the output wheel exactly as `plan` describes, without checking
+    /// again. Fails if the plan is from another version or its inputs changed.
+    pub fn apply(self, plan: &WheelPlan) -> Result<WheelWorkflowResult> {
+        apply(&self, plan)
+    }
+}
+
+fn run(args: &WheelBuilder) -> Result<WheelWorkflowResult> {
+    let session = Session::open(args, &args.wheel, args.output_wheel.as_deref())?;
+    let store = SummaryStore::new(args.cache.as_ref());
+    let systems = discover_systems(args, &store)?;
+    session.run(args, &store, &systems)
+}
+
+fn plan(args: &WheelBuilder) -> Result<WheelPlan> {
+    let wheel_sha256 = hash_file(&args.wheel)?;
+    let mut session = Session::open(args, &args.wheel, None)?;
+    let store = SummaryStore::new(args.cache.as_ref());
+    let systems = discover_systems(args, &store)?;
+    let (exec, removed) = session.analyze(args, &store, &systems)?;
+    let rel = |path: &Path| session.rel_name(path);
+
+    let mut edits = Vec::new();
+    for replacement in &exec.replacements {
+        edits.push(PlannedEdit {
+            extension: rel(&replacement.extension),
+            old_needed: replacement.old_needed.clone(),
+            new_needed: replacement.new_needed.clone(),
+            system_path: match &replacement.provider {
+                Some(provider) => provider.location(),
+                None => replacement.system_path.display().to_string(),
+            },
+            system_sha256: hash_file(&replacement.system_path)?,
+            provider: replacement.provider.clone(),
+            runpath: replacement.runpath.clone(),
+        });
+    }
+    let checks = exec
+        .outcomes
+        .iter()
+        .map(|outcome| PlannedCheck {
+            extension: rel(&outcome.extension),
+            bundled_soname: outcome.bundled_soname.clone(),
+            system_path: outcome.system_path.clone(),
+            verdict: outcome.verdict,
+            patched: outcome.patched,
+        })
+        .collect();
+    let mut warnings = session.warnings;
+    warnings.extend(exec.warnings);
+
+    Ok(WheelPlan {
+        unrepair_version: env!("CARGO_PKG_VERSION").to_string(),
+        wheel: args.wheel.display().to_string(),
+        wheel_sha256,
+        strict: args.strict,
+        transactional: args.transactional,
+        rolled_back: exec.rolled_back,
+        pairs: exec.pairs,
+        checks,
+        edits,
+        remove: removed,
+        pruned: exec.pruned,
+        pruned_bytes: exec.pruned_bytes,
+        failures: exec.failures,
+        warnings,
+    })
+}
+
+fn apply(args: &WheelBuilder, plan: &WheelPlan) -> Result<WheelWorkflowResult> {
+    let version = env!("CARGO_PKG_VERSION");
+    if plan.unrepair_version != version {
+        return Err(Error::InvalidArgument(format!(
+            "the plan was made by unrepair {}, this is unrepair {version}: make the plan again",
+            plan.unrepair_version
+        )));
+    }
+    let wheel_sha256 = hash_file(&args.wheel)?;
+    if wheel_sha256 != plan.wheel_sha256 {
+        return Err(Error::wheel(
+            &args.wheel,
+            format!(
+                "does not match the plan: sha256 is {wheel_sha256}, the plan was made for {}",
+                plan.wheel_sha256
+            ),
+        ));
+    }
+    // Libraries from provider wheels are extracted again to be hashed.
+    let scratch = create_workdir(args.workdir.as_deref())?;
+    let mut system_files = Vec::new();
+    for edit in &plan.edits {
+        let path = match &edit.provider {
+            Some(provider) => provider.materialize(scratch.path(), args.limits)?,
+            None => PathBuf::from(&edit.system_path),
+        };
+        if hash_file(&path)? != edit.system_sha256 {
+            return Err(Error::InvalidArgument(format!(
+                "system library {} has changed since the plan was made",
+                edit.system_path
+            )));
+        }
+        system_files.push(path);
+    }
+
+    let mut session = Session::open(args, &args.wheel, args.output_wheel.as_deref())?;
+    let exec = session.replay(args, plan, &system_files)?;
+    if exec.rolled_back {
+        session
+            .warnings
+            .push(rolled_back_warning(&exec, &session.output_wheel));
+    } else {
+        session.write(args, &exec, &plan.remove)?;
+    }
+    let mut result = session.result(args, exec, plan.remove.clone());
+    result.strict = plan.strict;
+    result.transactional = plan.transactional;
+    Ok(result)
+}
+
+/// An input wheel with its shared objects extracted to scratch space.
+struct Session {
+    wheel: PathBuf,
+    /// Keeps the scratch directory alive for the length of the run.
+    _tmp: TempDir,
+    root: PathBuf,
+    archive: WheelArchive,
+    layout: WheelLayout,
+    output_layout: WheelLayout,
+    output_wheel: PathBuf,
+    mtime: Option<DateTime>,
+    warnings: Vec<String>,
+}
+
+impl Session {
+    fn open(args: &WheelBuilder, wheel: &Path, output_wheel: Option<&Path>) -> Result<Self> {
+        if let Some(Some(label)) = &args.local_version {
+            if !is_valid_local_version(label) {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid local version label '{label}': use ASCII letters and digits separated by dots"
+                )));
+            }
+        }
+        let mtime = source_date_epoch(args.source_date_epoch)?.map(zip_time_from_epoch);
+        let tmp = create_workdir(args.workdir.as_deref())?;
+        let root = tmp.path().join("wheel-root");
+        fs::create_dir_all(&root).map_err(Error::io(&root))?;
+
+        args.progress.stage("Discovering wheel contents");
+        let mut archive = WheelArchive::open(wheel, args.limits)?;
+        let layout = archive.layout().clone();
+        let output_layout = layout.relabel(&args.retag, args.label_or(DEFAULT_LOCAL_VERSION));
+        let output_wheel = output_wheel
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| default_output_wheel(wheel, &output_layout));
+        let mut warnings = Vec::new();
+        let record_problems = archive.verify_record()?;
+        if !record_problems.is_empty() {
+            if !args.ignore_record_errors {
+                return Err(Error::RecordMismatch {
+                    path: wheel.to_path_buf(),
+                    problems: record_problems,
+                });
+            }
+            warnings.extend(
+                record_problems
+                    .into_iter()
+                    .map(|problem| format!("RECORD: {problem}")),
+            );
+        }
+        warnings.extend(archive.signatures().into_iter().map(|name| {
+            format!("Dropped signature {name}: it no longer matches the rewritten RECORD")
+        }));
+        archive.extract_elf_objects(&root)?;
+
+        Ok(Self {
+            wheel: wheel.to_path_buf(),
+            _tmp: tmp,
+            root,
+            archive,
+            layout,
+            output_layout,
+            output_wheel,
+            mtime,
+            warnings,
+        })
+    }
+
+    /// Analyze and write the output wheel, unless the run is rolled back.
+    fn run(
+        mut self,
+        args: &WheelBuilder,
+        store: &SummaryStore<'_>,
+        systems: &[SystemCandidate],
+    ) -> Result<WheelWorkflowResult> {
+        let (exec, removed) = self.analyze(args, store, systems)?;
+        if exec.rolled_back {
+            self.warnings
+                .push(rolled_back_warning(&exec, &self.output_wheel));
+        } else {
+            self.write(args, &exec, &removed)?;
+        }
+        Ok(self.result(args, exec, removed))
+    }
+
+    fn analyze(
+        &mut self,
+        args: &WheelBuilder,
+        store: &SummaryStore<'_>,
+        systems: &[SystemCandidate],
+    ) -> Result<(MappingExecution, Vec<String>)> {
+        let wheel = &self.wheel;
+        analyze(&self.root, &self.layout, args, store, systems, &|reason| {
+            Error::wheel(wheel, reason)
+        })
+    }
+
+    /// Archive name of an extracted file.
+    fn rel_name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .map(rel_string)
+            .unwrap_or_else(|_| path.display().to_string())
+    }
+
+    // `system_files` holds the verified library file for each edit.
+    fn replay(
+        &self,
+        args: &WheelBuilder,
+        plan: &WheelPlan,
+        system_files: &[PathBuf],
+    ) -> Result<MappingExecution> {
+        args.progress.stage("Applying plan");
+        let entry_path = |name: &str| {
+            // Only names of entries in the wheel are accepted, and those were
+            // validated as safe to extract when the archive was opened.
+            if self.archive.contains(name) {
+                Ok(self.root.join(name))
+            } else {
+                Err(Error::wheel(
+                    &self.wheel,
+                    format!("plan refers to {name}, which is not in the wheel"),
+                ))
+            }
+        };
+
+        let mut patched_files = BTreeSet::new();
+        let mut replacements = Vec::new();
+        if !plan.rolled_back {
+            for (edit, system_file) in plan.edits.iter().zip(system_files) {
+                let ext = entry_path(&edit.extension)?;
+                crate::patch::replace_needed(&ext, &ext, &edit.old_needed, &edit.new_needed)?;
+                if let Some(runpath) = &edit.runpath {
+                    crate::patch::add_runpath(&ext, &ext, std::slice::from_ref(runpath))?;
+                }
+                patched_files.insert(ext.clone());
+                replacements.push(Replacement {
+                    extension: ext,
+                    old_needed: edit.old_needed.clone(),
+                    new_needed: edit.new_needed.clone(),
+                    system_path: system_file.clone(),
+                    provider: edit.provider.clone(),
+                    runpath: edit.runpath.clone(),
+                });
+            }
+            for name in &plan.remove {
+                let path = entry_path(name)?;
+                fs::remove_file(&path).map_err(Error::io(&path))?;
+            }
+        }
+
+        let outcomes = plan
+            .checks
+            .iter()
+            .map(|check| CheckOutcome {
+                extension: self.root.join(&check.extension),
+                bundled_soname: check.bundled_soname.clone(),
+                system_path: check.system_path.clone(),
+                verdict: check.verdict,
+                patched: check.patched,
+            })
+            .collect::<Vec<_>>();
+        let compatible = |outcome: &&CheckOutcome| outcome.verdict == Verdict::Compatible;
+        // Session::open has already reported the RECORD and signature
+        // warnings again, so only the planned mapping warnings are kept.
+        Ok(MappingExecution {
+            pairs: plan.pairs.clone(),
+            warnings: plan
+                .warnings
+                .iter()
+                .filter(|warning| {
+                    !warning.starts_with("RECORD: ") && !warning.starts_with("Dropped signature ")
+                })
+                .cloned()
+                .collect(),
+            failures: plan.failures.clone(),
+            checked_extensions: outcomes.len(),
+            patched_extensions: replacements.len(),
+            skipped_checks: outcomes.iter().filter(|o| !compatible(o)).count(),
+            patched_bundled_sonames: HashSet::new(),
+            patched_files,
+            held_back_extensions: outcomes
+                .iter()
+                .filter(|o| compatible(o) && !o.patched)
+                .count(),
+            replacements,
+            rolled_back: plan.rolled_back,
+            outcomes,
+            pruned: plan.pruned.clone(),
+            pruned_bytes: plan.pruned_bytes,
+        })
+    }
+
+    fn write(
+        &mut self,
+        args: &WheelBuilder,
+        exec: &MappingExecution,
+        removed: &[String],
+    ) -> Result<()> {
+        args.progress.stage("Repacking wheel");
+        let layout = &self.layout;
+        let output_layout = &self.output_layout;
+        let mut changes = Changes {
+            modified: exec
+                .patched_files
+                .iter()
+                .filter_map(|path| path.strip_prefix(&self.root).ok())
+                .map(rel_string)
+                .collect(),
+            removed: removed.iter().cloned().collect(),
+            ..Changes::default()
+        };
+        let provenance = build_provenance(&self.root, layout, output_layout, exec, removed)?;
+        let provenance_file = format!("{}/{}", layout.dist_info, PROVENANCE_FILE);
+        if self.archive.contains(&provenance_file) {
+            changes
+                .generated
+                .insert(provenance_file, provenance.to_json());
+        } else {
+            changes.added.push((
+                format!("{}/{}", output_layout.dist_info, PROVENANCE_FILE),
+                provenance.to_json(),
+            ));
+        }
+        self.update_metadata(&mut changes, |text| provenance.add_metadata_headers(text))?;
+        self.archive.rewrite(
+            &self.output_wheel,
+            &self.root,
+            &changes,
+            &self.output_layout,
+            self.mtime,
+        )
+    }
+
+    fn update_metadata(
+        &mut self,
+        changes: &mut Changes,
+        edit: impl FnOnce(&str) -> String,
+    ) -> Result<()> {
+        let layout = &self.layout;
+        let output_layout = &self.output_layout;
+        let metadata = format!("{}/METADATA", layout.dist_info);
+        let text = self.archive.read_text(&metadata)?;
+        let mut new_text = edit(&text);
+        if output_layout.name.version != layout.name.version {
+            new_text = set_metadata_version(&new_text, &output_layout.name.version);
+        }
+        if new_text != text {
+            changes.generated.insert(metadata, new_text.into_bytes());
+        }
+        if output_layout.metadata.tags != layout.metadata.tags {
+            let wheel_file = format!("{}/WHEEL", layout.dist_info);
+            let text = self.archive.read_text(&wheel_file)?;
+            changes.generated.insert(
+                wheel_file,
+                set_wheel_tags(&text, &output_layout.metadata.tags).into_bytes(),
+            );
+        }
+        Ok(())
+    }
+
+    fn result(
+        self,
+        args: &WheelBuilder,
+        exec: MappingExecution,
+        removed: Vec<String>,
+    ) -> WheelWorkflowResult {
+        let mut warnings = self.warnings;
+        warnings.extend(exec.warnings);
+        WheelWorkflowResult {
+            input_wheel: self.wheel.display().to_string(),
+            output_wheel: self.output_wheel.display().to_string(),
+            strict: args.strict,
+            transactional: args.transactional,
+            hard_failure: exec.rolled_back,
+            rolled_back: exec.rolled_back,
+            failures: exec.failures,
+            warnings,
+            summary: WheelSummary {
+                matched_pairs: exec.pairs.len(),
+                checked_extensions: exec.checked_extensions,
+                patched_extensions: exec.patched_extensions,
+                held_back_extensions: exec.held_back_extensions,
+                removed_bundled_libs: removed.len(),
+                skipped_checks: exec.skipped_checks,
+                pruned_bundled_libs: exec.pruned.len(),
+                pruned_bytes: exec.pruned_bytes,
+            },
+            pairs: exec.pairs,
+            removed_bundled_paths: removed,
+        }
+    }
+}
+
+// Problems with the input itself are reported through `fail`.
+fn analyze(
+    root: &Path,
+    layout: &WheelLayout,
+    args: &WheelBuilder,
+    store: &SummaryStore<'_>,
+    systems: &[SystemCandidate],
+    fail: &dyn Fn(String) -> Error,
+) -> Result<(MappingExecution, Vec<String>)> {
+    let progress = args.progress.as_ref();
+    let objects = classify_objects(root, layout, store)?;
+    let bundled = bundled_libs(&objects, store)?;
+    // Package libraries and executables can link to bundled libraries too.
+    let extensions = consumers(&objects);
+
+    let count = |kind: ObjectKind| objects.iter().filter(|object| object.kind == kind).count();
+    progress.detail(&format!(
+        "Found {} extension module(s), {} package library file(s), {} executable(s) and {} \
+         bundled library file(s)",
+        count(ObjectKind::Extension),
+        count(ObjectKind::InternalLibrary),
+        count(ObjectKind::Executable),
+        bundled.len()
+    ));
+
+    progress.stage("Matching vendored libs to system libs");
+    let mappings = build_mappings(&bundled, systems).map_err(fail)?;
+    if mappings.is_empty() && !args.prune_unused {
+        return Err(fail(
+            "no bundled libraries matched provided system libraries".to_string(),
+        ));
+    }
+
+    progress.stage("Validating ABI and patching extensions");
+    let mut ext_needed = build_extension_needed_cache(&extensions, store, args.jobs)?;
+    let mut exec = execute_mappings(mappings, &extensions, &mut ext_needed, store, args)?;
+    if exec.rolled_back {
+        return Ok((exec, Vec::new()));
+    }
+    add_provider_runpaths(root, layout, &mut exec.replacements, store, fail)?;
+
+    progress.stage("Removing unneeded bundled libs");
+    let mut removed = remove_safely_unneeded_bundled(
+        root,
+        layout,
+        &ext_needed,
+        &exec.patched_bundled_sonames,
+        store,
+    )?;
+    if args.prune_unused {
+        progress.stage("Pruning unreachable bundled libs");
+        let (pruned, pruned_bytes) = prune_unreachable_bundled(root, layout, &extensions, store)?;
+        removed.extend(pruned.iter().cloned());
+        removed.sort();
+        exec.pruned = pruned;
+        exec.pruned_bytes = pruned_bytes;
+    }
+    Ok((exec, removed))
+}
+
+fn add_provider_runpaths(
+    root: &Path,
+    layout: &WheelLayout,
+    replacements: &mut [Replacement],
+    store: &SummaryStore<'_>,
+    fail: &dyn Fn(String) -> Error,
+) -> Result<()> {
+    for replacement in replacements {
+        let Some(provider) = &replacement.provider else {
+            continue;
+        };
+        let rel = replacement
+            .extension
+            .strip_prefix(root)
+            .map(rel_string)
+            .unwrap_or_else(|_| replacement.extension.display().to_string());
+        let Some((_, installed)) = layout
+            .locate(&rel)
+            .filter(|(scheme, _)| scheme.is_importable())
+        else {
+            return Err(fail(format!(
+                "{rel} uses {} from {} but is not installed to site-packages",
+                replacement.new_needed,
+                provider.requirement()
+            )));
+        };
+        let runpath = provider.runpath_from(installed);
+        crate::patch::add_runpath(
+            &replacement.extension,
+            &replacement.extension,
+            std::slice::from_ref(&runpath),
+        )?;
+        store.invalidate(&replacement.extension);
+        replacement.runpath = Some(runpath);
+    }
+    Ok(())
+}
+
+// Keeps the libraries extracted from provider wheels alive for the run.
+struct Systems {
+    candidates: Vec<SystemCandidate>,
+    _extracted: Option<TempDir>,
+}
+
+impl std::ops::Deref for Systems {
+    type Target = [SystemCandidate];
+
+    fn deref(&self) -> &[SystemCandidate] {
+        &self.candidates
+    }
+}
+
+fn discover_systems(args: &WheelBuilder, store: &SummaryStore<'_>) -> Result<Systems> {
+    let systems = discover_candidates(args, store)?;
+    if systems.is_empty() && !args.prune_unused {
+        return Err(Error::InvalidArgument(
+            "no usable system libraries found from --system-lib/--system-lib-dir/--provider-wheel/--provider-dist"
+                .to_string(),
+        ));
+    }
+    Ok(systems)
+}
+
+fn discover_candidates(args: &WheelBuilder, store: &SummaryStore<'_>) -> Result<Systems> {
+    let extracted = if args.providers.is_empty() {
+        None
+    } else {
+        Some(create_workdir(args.workdir.as_deref())?)
+    };
+    let mut provided = Vec::new();
+    for (index, source) in args.providers.iter().enumerate() {
+        let dir = extracted
+            .as_ref()
+            .expect("created when there are providers");
+        let scratch = dir.path().join(format!("provider-{index}"));
+        fs::create_dir_all(&scratch).map_err(Error::io(&scratch))?;
+        provided.extend(provided_libraries(source, &scratch, args.limits)?);
+    }
+    let candidates = discover_system_candidates(
+        &args.system_libs,
+        &args.system_lib_dirs,
+        provided,
+        store,
+        args.jobs,
+    )?;
+    Ok(Systems {
+        candidates,
+        _extracted: extracted,
+    })
+}
+
+fn rolled_back_warning(exec: &MappingExecution, output_wheel: &Path) -> String {
+    format!(
+        "Rolled back: {} failure(s), no extension was patched and {} was not written",
+        exec.failures.len(),
+        output_wheel.display()
+    )
+}
+
+fn build_provenance(
+    root: &Path,
+    layout: &WheelLayout,
+    output_layout: &WheelLayout,
+    exec: &MappingExecution,
+    removed: &[String],
+) -> Result<Provenance> {
+    let mut replacements = Vec::new();
+    let mut system_paths = BTreeMap::new();
+    for replacement in &exec.replacements {
+        let rel = replacement
+            .extension
+            .strip_prefix(root)
+            .map(rel_string)
+            .unwrap_or_else(|_| replacement.extension.display().to_string());
+        replacements.push(NeededReplacement {
+            extension: layout.rename_entry(output_layout, &rel),
+            old_needed: replacement.old_needed.clone(),
+            new_needed: replacement.new_needed.clone(),
+            runpath: replacement.runpath.clone(),
+        });
+        system_paths
+            .entry(replacement.new_needed.clone())
+            .or_insert((&replacement.system_path, replacement.provider.as_ref()));
+    }
+    let system_libraries = system_paths
+        .iter()
+        .map(|(soname, (path, provider))| SystemLibrary::describe(soname, path, *provider))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Provenance::new(
+        layout,
+        removed.to_vec(),
+        replacements,
+        system_libraries,
+    ))
+}
+
+/// One extension that depends on the bundled side of one mapping.
+struct CheckJob<'m> {
+    pair: usize,
+    extension: usize,
+    bundled: &'m BundledLib,
+    system: &'m SystemCandidate,
+}
+
+fn execute_mappings(
+    mappings: Vec<(&BundledLib, &SystemCandidate)>,
+    extensions: &[PathBuf],
+    ext_needed: &mut [HashSet<String>],
+    store: &SummaryStore<'_>,
+    args: &WheelBuilder,
+) -> Result<MappingExecution> {
+    // Checks only look at symbols and versions, which patching DT_NEEDED does
+    // not change, so they all run up front in parallel against the original
+    // files. Knowing every verdict before patching is also what lets the
+    // all-consumers and transactional policies hold changes back. Patching
+    // then happens sequentially in mapping order.
+    let mut pairs = Vec::new();
+    let mut check_jobs = Vec::new();
+    for (pair_idx, (bundled_lib, system_lib)) in mappings.iter().enumerate() {
+        let mut pair = PairResult {
+            bundled_path: rel_string(&bundled_lib.rel_path),
+            bundled_soname: bundled_lib.soname.clone(),
+            system_path: system_lib.describe(),
+            system_soname: system_lib.soname.clone(),
+            checked_extensions: 0,
+            patched_extensions: 0,
+            held_back_extensions: 0,
+            skipped_extensions: 0,
+            incompatible_extensions: 0,
+        };
+        for (idx, needed) in ext_needed.iter().enumerate() {
+            if needed.contains(&bundled_lib.soname) {
+                check_jobs.push(CheckJob {
+                    pair: pair_idx,
+                    extension: idx,
+                    bundled: bundled_lib,
+                    system: system_lib,
+                });
+            } else {
+                pair.skipped_extensions += 1;
+            }
+        }
+        pairs.push(pair);
+    }
+
+    let results = parallel::map(&check_jobs, args.jobs, |job| {
+        run_check(
+            &extensions[job.extension],
+            job.bundled,
+            job.system,
+            &args.checks,
+            store,
+        )
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+    let failed_pairs = check_jobs
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.verdict != Verdict::Compatible)
+        .map(|(job, _)| job.pair)
+        .collect::<HashSet<_>>();
+    let rolled_back = args.transactional && !failed_pairs.is_empty();
+
+    let mut failures = Vec::new();
+    let mut checked_extensions = 0usize;
+    let mut patched_extensions = 0usize;
+    let mut skipped_checks = 0usize;
+    let mut patched_bundled_sonames = HashSet::new();
+    let mut patched_files = BTreeSet::new();
+    let mut replacements = Vec::new();
+    let mut held_back_extensions = 0usize;
+    let mut warnings = Vec::new();
+    let mut outcomes = Vec::new();
+
+    for (job, check_result) in check_jobs.iter().zip(results) {
+        let ext = &extensions[job.extension];
+        let old_needed = &job.bundled.soname;
+        let new_needed = &job.system.soname;
+        let pair = &mut pairs[job.pair];
+
+        pair.checked_extensions += 1;
+        checked_extensions += 1;
+        let mut outcome = CheckOutcome {
+            extension: ext.clone(),
+            bundled_soname: old_needed.clone(),
+            system_path: job.system.describe(),
+            verdict: check_result.verdict,
+            patched: false,
+        };
+
+        if check_result.verdict == Verdict::Compatible
+            && (rolled_back || (args.all_consumers && failed_pairs.contains(&job.pair)))
+        {
+            pair.held_back_extensions += 1;
+            held_back_extensions += 1;
+            if !rolled_back {
+                warnings.push(format!(
+                    "{} kept on bundled {}: another extension using it is incompatible with system {}",
+                    ext.display(),
+                    old_needed,
+                    job.system.describe()
+                ));
+            }
+        } else if check_result.verdict == Verdict::Compatible {
+            crate::patch::replace_needed(ext, ext, old_needed, new_needed)?;
+            store.invalidate(ext);
+            ext_needed[job.extension].remove(old_needed);
+            ext_needed[job.extension].insert(new_needed.clone());
+
+            pair.patched_extensions += 1;
+            patched_extensions += 1;
+            patched_bundled_sonames.insert(old_needed.clone());
+            patched_files.insert(ext.clone());
+            replacements.push(Replacement {
+                extension: ext.clone(),
+                old_needed: old_needed.clone(),
+                new_needed: new_needed.clone(),
+                system_path: job.system.path.clone(),
+                provider: job.system.provider.clone(),
+                runpath: None,
+            });
+            outcome.patched = true;
+        } else {
+            pair.incompatible_extensions += 1;
+            skipped_checks += 1;
+            failures.push(format!(
+                "{} incompatible with system {}",
+                ext.display(),
+                job.system.describe()
+            ));
+        }
+        outcomes.push(outcome);
+    }
+
+    warnings.extend(
+        pairs
+            .iter()
+            .filter(|pair| pair.checked_extensions == 0)
+            .map(|pair| {
+                format!(
+                    "No extension depended on bundled {} ({})",
+                    pair.bundled_soname, pair.bundled_path
+                )
+            }),
+    );
+
+    Ok(MappingExecution {
+        pairs,
+        warnings,
+        failures,
+        checked_extensions,
+        patched_extensions,
+        skipped_checks,
+        patched_bundled_sonames,
+        patched_files,
+        replacements,
+        held_back_extensions,
+        rolled_back,
+        outcomes,
+        pruned: Vec::new(),
+        pruned_bytes: 0,
+    })
+}
+
+fn run_check(
+    extension: &Path,
+    bundled: &BundledLib,
+    system: &SystemCandidate,
+    checks: &CheckRegistry,
+    store: &SummaryStore<'_>,
+) -> Result<AbiCheckResult> {
+    let memo = store
+        .cache()
+        .map(|cache| {
+            cache
+                .check_key(extension, &bundled.abs_path, &system.path, checks)
+                .map(|key| (cache, key))
+        })
+        .transpose()?;
+    if let Some((cache, key)) = &memo {
+        if let Some(result) = cache.cached_check(key) {
+            return Ok(result);
+        }
+    }
+
+    let result = check_summaries(
+        &*store.get(extension)?,
+        &*store.get(&bundled.abs_path)?,
+        &*store.get(&system.path)?,
+        checks,
+    )?;
+    if let Some((cache, key)) = &memo {
+        cache.store_check(key, &result);
+    }
+    Ok(result)
+}
+
+fn build_extension_needed_cache(
+    extensions: &[PathBuf],
+    store: &SummaryStore<'_>,
+    jobs: usize,
+) -> Result<Vec<HashSet<String>>> {
+    parallel::map(extensions, jobs, |path| {
+        store
+            .get(path)
+            .map(|summary| summary.needed.iter().cloned().collect())
+    })
+    .into_iter()
+    .collect()
+}
+
+fn remove_safely_unneeded_bundled(
+    root: &Path,
+    layout: &WheelLayout,
+    ext_needed: &[HashSet<String>],
+    patched_bundled_sonames: &HashSet<String>,
+    store: &SummaryStore<'_>,
+) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let mut bundled_by_soname = discover_bundled_libs(root, layout, store)?
+        .into_iter()
+        .map(|lib| (lib.soname.clone(), lib))
+        .collect::<HashMap<_, _>>();
+
+    loop {
+        let current_sonames = bundled_by_soname.keys().cloned().collect::<HashSet<_>>();
+
+        let needed_by_extensions = ext_needed
+            .iter()
+            .flat_map(|set| set.iter())
+            .filter(|name| current_sonames.contains(*name))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        let mut needed_by_bundled = HashSet::new();
+        for lib in bundled_by_soname.values() {
+            for needed in &store.get(&lib.abs_path)?.needed {
+                if current_sonames.contains(needed) {
+                    needed_by_bundled.insert(needed.clone());
+                }
+            }
+        }
+
+        let removable = bundled_by_soname
+            .iter()
+            .filter(|(soname, _)| patched_bundled_sonames.contains(*soname))
+            .filter(|(soname, _)| {
+                !needed_by_extensions.contains(*soname) && !needed_by_bundled.contains(*soname)
+            })
+            .map(|(soname, lib)| (soname.clone(), lib.clone()))
+            .collect::<Vec<_>>();
+
+        if removable.is_empty() {
+            break;
+        }
+
+        for (soname, lib) in removable {
+            fs::remove_file(&lib.abs_path).map_err(Error::io(&lib.abs_path))?;
+            store.invalidate(&lib.abs_path);
+            removed.push(rel_string(&lib.rel_path));
+            bundled_by_soname.remove(&soname);
+        }
+    }
+
+    removed.sort();
+    Ok(removed)
+}
+
+// Reachability follows `DT_NEEDED` entries and `dlopen` names.
+fn prune_unreachable_bundled(
+    root: &Path,
+    layout: &WheelLayout,
+    extensions: &[PathBuf],
+    store: &SummaryStore<'_>,
+) -> Result<(Vec<String>, u64)> {
+    let bundled = discover_bundled_libs(root, layout, store)?;
+    let mut pending = Vec::new();
+    for ext in extensions {
+        let summary = store.get(ext)?;
+        pending.extend(summary.needed.iter().chain(&summary.dlopen_names).cloned());
+    }
+    let mut reached = vec![false; bundled.len()];
+    while let Some(name) = pending.pop() {
+        for (idx, lib) in bundled.iter().enumerate() {
+            let matches = lib.soname == name
+                || lib
+                    .rel_path
+                    .file_name()
+                    .is_some_and(|file| file == name.as_str());
+            if reached[idx] || !matches {
+                continue;
+            }
+            reached[idx] = true;
+            let summary = store.get(&lib.abs_path)?;
+            pending.extend(summary.needed.iter().chain(&summary.dlopen_names).cloned());
+        }
+    }
+
+    let mut pruned = Vec::new();
+    let mut bytes = 0;
+    for (lib, reached) in bundled.iter().zip(reached) {
+        if reached {
+            continue;
+        }
+        let metadata = fs::metadata(&lib.abs_path).map_err(Error::io(&lib.abs_path))?;
+        bytes += metadata.len();
+        fs::remove_file(&lib.abs_path).map_err(Error::io(&lib.abs_path))?;
+        store.invalidate(&lib.abs_path);
+        pruned.push(rel_string(&lib.rel_path));
+    }
+    Ok((pruned, bytes))
+}
+
+fn source_date_epoch(explicit: Option<u64>) -> Result<Option<u64>> {
+    if explicit.is_some() {
+        return Ok(explicit);
+    }
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.trim().is_empty() => value.trim().parse().map(Some).map_err(|_| {
+            Error::InvalidArgument(format!(
+                "SOURCE_DATE_EPOCH must be a non-negative integer, got {value:?}"
+            ))
+        }),
+        _ => Ok(None),
+    }
+}
+
+fn create_workdir(base: Option<&Path>) -> Result<TempDir> {
+    match base {
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(Error::io(dir))?;
+            tempfile::Builder::new()
+                .prefix(SCRATCH_PREFIX)
+                .tempdir_in(dir)
+                .map_err(Error::io(dir))
+        }
+        None => tempfile::Builder::new()
+            .prefix(SCRATCH_PREFIX)
+            .tempdir()
+            .map_err(Error::io(&std::env::temp_dir())),
+    }
+}
+
+/// Next to `input`, or `<input stem>.unrepaired.whl` if the name is unchanged.
+pub fn default_output_wheel(input: &Path, output: &WheelLayout) -> PathBuf {
+    let parent = input.parent().unwrap_or_else(|| Path::new("."));
+    let renamed = parent.join(output.name.to_string());
+    if renamed != input {
+        return renamed;
+    }
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    parent.join(format!("{stem}.unrepaired.whl"))
+}
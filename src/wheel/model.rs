@@ -0,0 +1,535 @@
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// `{distribution}-{version}(-{build})?-{python}-{abi}-{platform}.whl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelName {
+    pub distribution: String,
+    pub version: String,
+    pub build: Option<String>,
+    /// Python tag, possibly a compressed set such as `py2.py3`.
+    pub python_tag: String,
+    pub abi_tag: String,
+    pub platform_tag: String,
+}
+
+impl WheelName {
+    /// Parse a file name such as `numpy-2.0.0-cp312-cp312-manylinux_2_28_x86_64.whl`.
+    pub fn parse(file_name: &str) -> Option<Self> {
+        let stem = file_name.strip_suffix(".whl")?;
+        let parts = stem.split('-').collect::<Vec<_>>();
+        let (build, tags) = match parts.len() {
+            5 => (None, &parts[2..]),
+            6 if parts[2].starts_with(|c: char| c.is_ascii_digit()) => {
+                (Some(parts[2].to_string()), &parts[3..])
+            }
+            _ => return None,
+        };
+        if parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        Some(Self {
+            distribution: parts[0].to_string(),
+            version: parts[1].to_string(),
+            build,
+            python_tag: tags[0].to_string(),
+            abi_tag: tags[1].to_string(),
+            platform_tag: tags[2].to_string(),
+        })
+    }
+
+    /// Every `python-abi-platform` tag the compressed tag sets expand to.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = Vec::new();
+        for python in self.python_tag.split('.') {
+            for abi in self.abi_tag.split('.') {
+                for platform in self.platform_tag.split('.') {
+                    tags.push(format!("{python}-{abi}-{platform}"));
+                }
+            }
+        }
+        tags
+    }
+}
+
+impl fmt::Display for WheelName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.distribution, self.version)?;
+        if let Some(build) = &self.build {
+            write!(f, "-{build}")?;
+        }
+        write!(
+            f,
+            "-{}-{}-{}.whl",
+            self.python_tag, self.abi_tag, self.platform_tag
+        )
+    }
+}
+
+/// The `.dist-info/WHEEL` metadata file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelMetadata {
+    pub wheel_version: String,
+    pub generator: Option<String>,
+    /// Whether the archive root installs to purelib rather than platlib.
+    pub root_is_purelib: bool,
+    pub tags: Vec<String>,
+    pub build: Option<String>,
+    /// Any other headers, in file order.
+    pub other: Vec<(String, String)>,
+}
+
+impl WheelMetadata {
+    /// Parse `WHEEL` contents. Returns the reason on failure.
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut wheel_version = None;
+        let mut generator = None;
+        let mut root_is_purelib = None;
+        let mut tags = Vec::new();
+        let mut build = None;
+        let mut other = Vec::new();
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("malformed WHEEL line '{line}'"))?;
+            let value = value.trim().to_string();
+            match key.trim() {
+                "Wheel-Version" => wheel_version = Some(value),
+                "Generator" => generator = Some(value),
+                "Root-Is-Purelib" => {
+                    root_is_purelib = Some(match value.to_ascii_lowercase().as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(format!("invalid Root-Is-Purelib value '{value}'")),
+                    })
+                }
+                "Tag" => tags.push(value),
+                "Build" => build = Some(value),
+                key => other.push((key.to_string(), value)),
+            }
+        }
+
+        let wheel_version = wheel_version.ok_or("WHEEL has no Wheel-Version")?;
+        if !wheel_version.starts_with("1.") {
+            return Err(format!("unsupported Wheel-Version {wheel_version}"));
+        }
+        Ok(Self {
+            wheel_version,
+            generator,
+            root_is_purelib: root_is_purelib.ok_or("WHEEL has no Root-Is-Purelib")?,
+            tags,
+            build,
+            other,
+        })
+    }
+}
+
+/// How to change the platform tag of the output wheel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Retag {
+    /// Keep the input wheel's tags.
+    #[default]
+    Keep,
+    /// Replace `manylinux*` and `musllinux*` tags with `linux_<arch>`.
+    Linux,
+    /// Use this platform tag (or compressed tag set) instead.
+    Platform(String),
+}
+
+impl Retag {
+    fn platforms(&self, platform: &str) -> Vec<String> {
+        let mapped = match self {
+            Retag::Keep => platform.split('.').map(ToOwned::to_owned).collect(),
+            Retag::Linux => platform.split('.').map(generic_linux_platform).collect(),
+            Retag::Platform(tag) => tag.split('.').map(ToOwned::to_owned).collect::<Vec<_>>(),
+        };
+        dedup_in_order(mapped)
+    }
+}
+
+fn generic_linux_platform(tag: &str) -> String {
+    for prefix in ["manylinux_", "musllinux_"] {
+        if let Some(rest) = tag.strip_prefix(prefix) {
+            // {major}_{minor}_{arch}
+            if let Some(arch) = rest.splitn(3, '_').nth(2) {
+                return format!("linux_{arch}");
+            }
+        }
+    }
+    for prefix in ["manylinux1_", "manylinux2010_", "manylinux2014_"] {
+        if let Some(arch) = tag.strip_prefix(prefix) {
+            return format!("linux_{arch}");
+        }
+    }
+    tag.to_string()
+}
+
+fn dedup_in_order(items: Vec<String>) -> Vec<String> {
+    let mut out = Vec::with_capacity(items.len());
+    for item in items {
+        if !out.contains(&item) {
+            out.push(item);
+        }
+    }
+    out
+}
+
+/// ASCII alphanumerics separated by dots, as PEP 440 normalizes them.
+pub fn is_valid_local_version(label: &str) -> bool {
+    !label.is_empty()
+        && label
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Install location of a wheel entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Purelib,
+    Platlib,
+    Scripts,
+    Headers,
+    Data,
+    /// The `.dist-info` directory.
+    Metadata,
+}
+
+impl Scheme {
+    pub fn is_importable(self) -> bool {
+        matches!(self, Scheme::Purelib | Scheme::Platlib)
+    }
+}
+
+/// Name, metadata and directory layout of one wheel.
+#[derive(Debug, Clone)]
+pub struct WheelLayout {
+    pub name: WheelName,
+    pub metadata: WheelMetadata,
+    /// The top-level `{distribution}-{version}.dist-info` directory.
+    pub dist_info: String,
+    /// May not exist in the archive.
+    pub data_dir: String,
+}
+
+impl WheelLayout {
+    pub(crate) fn from_entries<'n>(
+        path: &Path,
+        names: impl IntoIterator<Item = &'n str>,
+        read_entry: impl FnOnce(&str) -> Result<String>,
+    ) -> Result<Self> {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let name = WheelName::parse(file_name)
+            .ok_or_else(|| Error::wheel(path, format!("invalid wheel file name '{file_name}'")))?;
+
+        let mut dist_infos = names
+            .into_iter()
+            .filter_map(|entry| entry.split('/').next())
+            .filter(|top| top.ends_with(".dist-info"))
+            .filter(|top| {
+                let stem = top.trim_end_matches(".dist-info");
+                stem.split_once('-').is_some_and(|(dist, version)| {
+                    canonical(dist) == canonical(&name.distribution) && version == name.version
+                })
+            })
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        dist_infos.sort();
+        dist_infos.dedup();
+        let dist_info = match dist_infos.as_slice() {
+            [one] => one.clone(),
+            [] => {
+                return Err(Error::wheel(
+                    path,
+                    format!(
+                        "no {}-{}.dist-info directory at the top of the wheel",
+                        name.distribution, name.version
+                    ),
+                ))
+            }
+            many => {
+                return Err(Error::wheel(
+                    path,
+                    format!("several matching .dist-info directories: {many:?}"),
+                ))
+            }
+        };
+
+        let wheel_text = read_entry(&format!("{dist_info}/WHEEL"))?;
+        let metadata = WheelMetadata::parse(&wheel_text)
+            .map_err(|reason| Error::wheel(path, format!("{dist_info}/WHEEL: {reason}")))?;
+        let data_dir = format!("{}.data", dist_info.trim_end_matches(".dist-info"));
+
+        Ok(Self {
+            name,
+            metadata,
+            dist_info,
+            data_dir,
+        })
+    }
+
+    /// Layout of an installed distribution, from its `.dist-info` and `WHEEL`.
+    pub(crate) fn installed(
+        dist_info: &str,
+        wheel_text: &str,
+    ) -> std::result::Result<Self, String> {
+        let metadata = WheelMetadata::parse(wheel_text)?;
+        let (distribution, version) = dist_info
+            .strip_suffix(".dist-info")
+            .and_then(|stem| stem.split_once('-'))
+            .ok_or_else(|| format!("'{dist_info}' is not named {{name}}-{{version}}.dist-info"))?;
+        let tag = metadata
+            .tags
+            .first()
+            .ok_or_else(|| format!("{dist_info}/WHEEL has no Tag"))?;
+        let mut parts = tag.splitn(3, '-');
+        let (Some(python_tag), Some(abi_tag), Some(platform_tag)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("{dist_info}/WHEEL: invalid tag '{tag}'"));
+        };
+        let name = WheelName {
+            distribution: distribution.to_string(),
+            version: version.to_string(),
+            build: metadata.build.clone(),
+            python_tag: python_tag.to_string(),
+            abi_tag: abi_tag.to_string(),
+            platform_tag: platform_tag.to_string(),
+        };
+        Ok(Self {
+            data_dir: format!("{distribution}-{version}.data"),
+            dist_info: dist_info.to_string(),
+            name,
+            metadata,
+        })
+    }
+
+    pub fn relabel(&self, retag: &Retag, local_version: Option<&str>) -> WheelLayout {
+        let mut out = self.clone();
+        if let Some(label) = local_version {
+            let separator = if self.name.version.contains('+') {
+                '.'
+            } else {
+                '+'
+            };
+            out.name.version = format!("{}{separator}{label}", self.name.version);
+            let (dist, _) = self
+                .dist_info
+                .split_once('-')
+                .unwrap_or((&self.name.distribution, ""));
+            out.dist_info = format!("{dist}-{}.dist-info", out.name.version);
+            out.data_dir = format!("{dist}-{}.data", out.name.version);
+        }
+
+        out.name.platform_tag = retag.platforms(&self.name.platform_tag).join(".");
+        let mut tags = Vec::new();
+        for tag in &self.metadata.tags {
+            let mut parts = tag.splitn(3, '-');
+            let (Some(python), Some(abi), Some(platform)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                tags.push(tag.clone());
+                continue;
+            };
+            for platform in retag.platforms(platform) {
+                tags.push(format!("{python}-{abi}-{platform}"));
+            }
+        }
+        out.metadata.tags = dedup_in_order(tags);
+        out
+    }
+
+    pub fn restore(&self, version: &str, tags: &[String]) -> WheelLayout {
+        let mut out = self.clone();
+        if version != self.name.version {
+            let (dist, _) = self
+                .dist_info
+                .split_once('-')
+                .unwrap_or((&self.name.distribution, ""));
+            out.name.version = version.to_string();
+            out.dist_info = format!("{dist}-{version}.dist-info");
+            out.data_dir = format!("{dist}-{version}.data");
+        }
+        let platforms = tags
+            .iter()
+            .filter_map(|tag| tag.splitn(3, '-').nth(2))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        if !platforms.is_empty() {
+            out.name.platform_tag = dedup_in_order(platforms).join(".");
+            out.metadata.tags = tags.to_vec();
+        }
+        out
+    }
+
+    pub fn rename_entry(&self, to: &WheelLayout, entry: &str) -> String {
+        if let Some(rest) = strip_dir(entry, &self.dist_info) {
+            return format!("{}/{rest}", to.dist_info);
+        }
+        if let Some(rest) = strip_dir(entry, &self.data_dir) {
+            return format!("{}/{rest}", to.data_dir);
+        }
+        entry.to_string()
+    }
+
+    pub fn record_name(&self) -> String {
+        format!("{}/RECORD", self.dist_info)
+    }
+
+    /// Whether `entry` is a `RECORD.jws` or `RECORD.p7s` signature.
+    pub fn is_signature(&self, entry: &str) -> bool {
+        entry
+            .strip_prefix(&self.dist_info)
+            .is_some_and(|rest| rest == "/RECORD.jws" || rest == "/RECORD.p7s")
+    }
+
+    /// `None` for unknown `.data` subdirectories.
+    pub fn locate<'e>(&self, entry: &'e str) -> Option<(Scheme, &'e str)> {
+        if let Some(rest) = strip_dir(entry, &self.dist_info) {
+            return Some((Scheme::Metadata, rest));
+        }
+        if let Some(rest) = strip_dir(entry, &self.data_dir) {
+            let (key, inner) = rest.split_once('/')?;
+            let scheme = match key {
+                "purelib" => Scheme::Purelib,
+                "platlib" => Scheme::Platlib,
+                "scripts" => Scheme::Scripts,
+                "headers" => Scheme::Headers,
+                "data" => Scheme::Data,
+                _ => return None,
+            };
+            return Some((scheme, inner));
+        }
+        let root = if self.metadata.root_is_purelib {
+            Scheme::Purelib
+        } else {
+            Scheme::Platlib
+        };
+        Some((root, entry))
+    }
+}
+
+fn strip_dir<'e>(entry: &'e str, dir: &str) -> Option<&'e str> {
+    entry.strip_prefix(dir)?.strip_prefix('/')
+}
+
+pub(crate) fn canonical(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !out.ends_with('_') {
+                out.push('_');
+            }
+        } else {
+            out.push(c.to_ascii_lowercase());
+        }
+    }
+    out
+}
+
+/// `METADATA` contents with the `Version` header replaced.
+pub(crate) fn set_metadata_version(text: &str, version: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut in_headers = true;
+    let mut replaced = false;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end().is_empty() {
+            in_headers = false;
+        }
+        if in_headers && !replaced && line.starts_with("Version:") {
+            out.push_str(&format!("Version: {version}\n"));
+            replaced = true;
+        } else {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+pub(crate) fn add_metadata_headers(text: &str, key: &str, values: &[String]) -> String {
+    let mut out = String::with_capacity(text.len() + values.len() * 32);
+    let mut lines = text.split_inclusive('\n');
+    let mut existing = HashSet::new();
+    let mut body = None;
+    for line in lines.by_ref() {
+        if line.trim_end().is_empty() {
+            body = Some(line);
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case(key) {
+                existing.insert(value.trim().to_string());
+            }
+        }
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    for value in values {
+        if existing.insert(value.clone()) {
+            out.push_str(&format!("{key}: {value}\n"));
+        }
+    }
+    if let Some(separator) = body {
+        out.push_str(separator);
+        out.extend(lines);
+    }
+    out
+}
+
+/// Values of the `key` headers in `METADATA` contents, in order.
+pub(crate) fn metadata_header_values(text: &str, key: &str) -> Vec<String> {
+    text.lines()
+        .take_while(|line| !line.trim_end().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+pub(crate) fn remove_metadata_headers(text: &str, key: &str, values: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_headers = true;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end().is_empty() {
+            in_headers = false;
+        }
+        let matches = in_headers
+            && line.split_once(':').is_some_and(|(name, value)| {
+                name.trim().eq_ignore_ascii_case(key) && values.iter().any(|v| v == value.trim())
+            });
+        if !matches {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+pub(crate) fn set_wheel_tags(text: &str, tags: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut written = false;
+    for line in text.split_inclusive('\n') {
+        if line
+            .split_once(':')
+            .is_some_and(|(key, _)| key.trim() == "Tag")
+        {
+            if !written {
+                for tag in tags {
+                    out.push_str(&format!("Tag: {tag}\n"));
+                }
+                written = true;
+            }
+        } else {
+            out.push_str(line);
+        }
+    }
+    if !written {
+        for tag in tags {
+            out.push_str(&format!("Tag: {tag}\n"));
+        }
+    }
+    out
+}
@@ -0,0 +1,89 @@
+use super::{outcome_is_complete, PairResult, ProvidedLibrary};
+use crate::error::{Error, Result};
+use crate::Verdict;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The changes a wheel run would make, as returned by [`super::WheelBuilder::plan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WheelPlan {
+    /// Version of unrepair that made the plan.
+    pub unrepair_version: String,
+    /// Input wheel the plan was made for.
+    pub wheel: String,
+    /// Hex SHA-256 of the input wheel.
+    pub wheel_sha256: String,
+    pub strict: bool,
+    pub transactional: bool,
+    /// A transactional plan with failures: applying it writes nothing.
+    pub rolled_back: bool,
+    pub pairs: Vec<PairResult>,
+    /// Verdict for every extension checked against a system library.
+    pub checks: Vec<PlannedCheck>,
+    /// `DT_NEEDED` entries to rewrite.
+    pub edits: Vec<PlannedEdit>,
+    /// Bundled libraries to leave out of the output.
+    pub remove: Vec<String>,
+    /// The entries of `remove` pruned as unreachable.
+    #[serde(default)]
+    pub pruned: Vec<String>,
+    /// Size of the pruned libraries.
+    #[serde(default)]
+    pub pruned_bytes: u64,
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Verdict for one extension against one system library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedCheck {
+    pub extension: String,
+    pub bundled_soname: String,
+    pub system_path: String,
+    pub verdict: Verdict,
+    /// False for compatible extensions that were held back.
+    pub patched: bool,
+}
+
+/// One `DT_NEEDED` rewrite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedEdit {
+    pub extension: String,
+    pub old_needed: String,
+    pub new_needed: String,
+    pub system_path: String,
+    /// Hex SHA-256 of the system library when the plan was made.
+    pub system_sha256: String,
+    /// Set when the library comes from a provider distribution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProvidedLibrary>,
+    /// RUNPATH entry to add to the extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runpath: Option<String>,
+}
+
+impl WheelPlan {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(Error::io(path))?;
+        serde_json::from_str(&text).map_err(|source| Error::Plan {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()).map_err(Error::io(path))
+    }
+
+    /// Pretty-printed JSON with a trailing newline.
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("plans always serialize to JSON");
+        json.push('\n');
+        json
+    }
+
+    pub fn is_complete(&self) -> bool {
+        outcome_is_complete(self.rolled_back, self.strict, &self.failures)
+    }
+}
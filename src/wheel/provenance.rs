@@ -0,0 +1,124 @@
+use super::model::{add_metadata_headers, WheelLayout};
+use super::provider::ProvidedLibrary;
+use crate::cache::hash_file;
+use crate::elf::build_id::read_build_id;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Name of the provenance file written into the output `.dist-info`.
+pub const PROVENANCE_FILE: &str = "unrepair.json";
+
+/// What unvendoring changed, stored as [`PROVENANCE_FILE`] in `.dist-info`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Version of unrepair that produced the wheel.
+    pub unrepair_version: String,
+    /// Paths inside the input wheel of bundled libraries that were removed.
+    pub removed_bundled: Vec<String>,
+    /// `DT_NEEDED` entries rewritten in extension modules.
+    pub replacements: Vec<NeededReplacement>,
+    /// System libraries the wheel now depends on instead.
+    pub system_libraries: Vec<SystemLibrary>,
+    /// Version of the input wheel, before any local version label was added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_version: Option<String>,
+    /// `WHEEL` tags of the input wheel, before any retagging.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub original_tags: Vec<String>,
+}
+
+/// One rewritten `DT_NEEDED` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NeededReplacement {
+    /// Path of the extension module inside the output wheel.
+    pub extension: String,
+    pub old_needed: String,
+    pub new_needed: String,
+    /// RUNPATH entry added so the extension finds a provided library.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runpath: Option<String>,
+}
+
+/// A library an extension was switched to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemLibrary {
+    pub soname: String,
+    pub path: String,
+    /// Hex SHA-256 of the library file.
+    pub sha256: String,
+    /// Hex GNU build-id, when the library has one.
+    pub build_id: Option<String>,
+    /// `Requires-Dist` of the provider, for non-system libraries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+}
+
+impl SystemLibrary {
+    // A provided library is recorded under its provider location, not `path`.
+    pub(crate) fn describe(
+        soname: &str,
+        path: &Path,
+        provider: Option<&ProvidedLibrary>,
+    ) -> Result<Self> {
+        Ok(Self {
+            soname: soname.to_string(),
+            path: provider
+                .map(ProvidedLibrary::location)
+                .unwrap_or_else(|| path.display().to_string()),
+            sha256: hash_file(path)?,
+            build_id: read_build_id(path)?,
+            provider: provider.map(ProvidedLibrary::requirement),
+        })
+    }
+}
+
+impl Provenance {
+    pub(crate) fn new(
+        original: &WheelLayout,
+        removed_bundled: Vec<String>,
+        replacements: Vec<NeededReplacement>,
+        system_libraries: Vec<SystemLibrary>,
+    ) -> Self {
+        Self {
+            unrepair_version: env!("CARGO_PKG_VERSION").to_string(),
+            removed_bundled,
+            replacements,
+            system_libraries,
+            original_version: Some(original.name.version.clone()),
+            original_tags: original.metadata.tags.clone(),
+        }
+    }
+
+    /// `Requires-External` and `Requires-Dist` headers for the new dependencies.
+    pub(crate) fn metadata_headers(&self) -> [(&'static str, Vec<String>); 2] {
+        let mut external = Vec::new();
+        let mut dists = Vec::new();
+        for lib in &self.system_libraries {
+            match &lib.provider {
+                Some(requirement) => dists.push(requirement.clone()),
+                None => external.push(lib.soname.clone()),
+            }
+        }
+        dists.sort();
+        dists.dedup();
+        [("Requires-External", external), ("Requires-Dist", dists)]
+    }
+
+    /// `METADATA` contents with the [`Provenance::metadata_headers`] added.
+    pub(crate) fn add_metadata_headers(&self, text: &str) -> String {
+        self.metadata_headers()
+            .iter()
+            .fold(text.to_string(), |text, (key, values)| {
+                add_metadata_headers(&text, key, values)
+            })
+    }
+
+    /// Pretty-printed JSON with a trailing newline.
+    pub(crate) fn to_json(&self) -> Vec<u8> {
+        let mut json =
+            serde_json::to_vec_pretty(self).expect("provenance always serializes to JSON");
+        json.push(b'\n');
+        json
+    }
+}
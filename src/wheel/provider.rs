@@ -0,0 +1,159 @@
+use super::archive::{ArchiveLimits, WheelArchive};
+use super::discover::{collect_files, is_shared_object_name, rel_string};
+use super::installed::{find_dist_info, is_inside};
+use super::record::parse_record;
+use super::split::origin_path_to;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// A distribution whose shared libraries can replace bundled ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderSource {
+    /// A provider wheel file.
+    Wheel(PathBuf),
+    /// A provider distribution installed in a site-packages directory.
+    Installed {
+        site_packages: PathBuf,
+        distribution: String,
+    },
+}
+
+/// A shared library from a provider distribution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvidedLibrary {
+    pub distribution: String,
+    pub version: String,
+    /// Path of the library relative to site-packages once installed.
+    pub path: String,
+    /// The provider wheel or site-packages directory.
+    pub source: String,
+    /// Name of the library's entry when `source` is a wheel.
+    pub wheel_entry: Option<String>,
+}
+
+impl ProvidedLibrary {
+    /// `Requires-Dist` value pinning the provider.
+    pub fn requirement(&self) -> String {
+        format!("{}=={}", self.distribution, self.version)
+    }
+
+    /// Where the library comes from, for reports.
+    pub fn location(&self) -> String {
+        match &self.wheel_entry {
+            Some(entry) => format!("{}:{}", self.source, entry),
+            None => Path::new(&self.source)
+                .join(&self.path)
+                .display()
+                .to_string(),
+        }
+    }
+
+    pub(crate) fn runpath_from(&self, installed: &str) -> String {
+        let dir = self.path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        origin_path_to(installed, dir)
+    }
+
+    // Plans are user input, so the path is checked to stay inside its source.
+    pub(crate) fn materialize(&self, scratch: &Path, limits: ArchiveLimits) -> Result<PathBuf> {
+        let source = Path::new(&self.source);
+        let entry = self.wheel_entry.as_deref().unwrap_or(&self.path);
+        if !is_inside(Path::new(entry)) {
+            return Err(Error::InvalidArgument(format!(
+                "provided library path '{entry}' leaves {}",
+                source.display()
+            )));
+        }
+        if self.wheel_entry.is_none() {
+            return Ok(source.join(entry));
+        }
+
+        let file = File::open(source).map_err(Error::io(source))?;
+        let mut archive = zip::ZipArchive::new(file).map_err(Error::zip(source))?;
+        let zipped = archive.by_name(entry).map_err(Error::zip(source))?;
+        let target = scratch.join(entry);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(Error::io(parent))?;
+        }
+        let mut out = File::create(&target).map_err(Error::io(&target))?;
+        let limit = limits.max_entry_size;
+        let written = io::copy(&mut zipped.take(limit.saturating_add(1)), &mut out)
+            .map_err(Error::io(&target))?;
+        if written > limit {
+            return Err(Error::UnsafeEntry {
+                path: source.to_path_buf(),
+                entry: entry.to_string(),
+                reason: format!("decompressed size exceeds the limit of {}", limit),
+            });
+        }
+        Ok(target)
+    }
+}
+
+pub(crate) fn provided_libraries(
+    source: &ProviderSource,
+    scratch: &Path,
+    limits: ArchiveLimits,
+) -> Result<Vec<(PathBuf, ProvidedLibrary)>> {
+    match source {
+        ProviderSource::Wheel(wheel) => {
+            let mut archive = WheelArchive::open(wheel, limits)?;
+            archive.extract_elf_objects(scratch)?;
+            let layout = archive.layout();
+            let mut out = Vec::new();
+            for rel in collect_files(scratch, false)? {
+                let entry = rel_string(&rel);
+                let Some((_, installed)) = layout
+                    .locate(&entry)
+                    .filter(|(scheme, _)| scheme.is_importable())
+                else {
+                    continue;
+                };
+                out.push((
+                    scratch.join(&rel),
+                    ProvidedLibrary {
+                        distribution: layout.name.distribution.clone(),
+                        version: layout.name.version.clone(),
+                        path: installed.to_string(),
+                        source: wheel.display().to_string(),
+                        wheel_entry: Some(entry.clone()),
+                    },
+                ));
+            }
+            Ok(out)
+        }
+        ProviderSource::Installed {
+            site_packages,
+            distribution,
+        } => {
+            let dist_info = find_dist_info(site_packages, distribution)?;
+            let (name, version) = dist_info
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+                .expect("find_dist_info only returns {name}-{version}.dist-info");
+            let record = site_packages.join(&dist_info).join("RECORD");
+            let text = fs::read_to_string(&record).map_err(Error::io(&record))?;
+            let mut out = Vec::new();
+            for (path, _) in parse_record(&text) {
+                let rel = Path::new(&path);
+                let file = site_packages.join(rel);
+                if !is_shared_object_name(rel.file_name()) || !is_inside(rel) || !file.is_file() {
+                    continue;
+                }
+                out.push((
+                    file,
+                    ProvidedLibrary {
+                        distribution: name.to_string(),
+                        version: version.to_string(),
+                        path,
+                        source: site_packages.display().to_string(),
+                        wheel_entry: None,
+                    },
+                ));
+            }
+            Ok(out)
+        }
+    }
+}
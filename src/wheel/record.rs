@@ -0,0 +1,111 @@
+use base64::Engine as _;
+use sha2::digest::DynDigest;
+use sha2::{Sha256, Sha384, Sha512};
+use std::io::{self, Write};
+
+pub(crate) fn parse_record(text: &str) -> Vec<(String, RecordLine)> {
+    let mut lines = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        // Hash and size never contain commas, so split from the right.
+        let mut fields = line.rsplitn(3, ',');
+        let (Some(size), Some(hash), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        lines.push((
+            unquote_record_path(path),
+            RecordLine {
+                hash: hash.to_string(),
+                size: size.to_string(),
+            },
+        ));
+    }
+    lines
+}
+
+/// One line of a RECORD file.
+pub(crate) struct RecordLine {
+    pub(crate) hash: String,
+    pub(crate) size: String,
+}
+
+impl RecordLine {
+    // RECORD itself and signatures have no hash or size.
+    pub(crate) fn is_complete(&self) -> bool {
+        !self.hash.is_empty() && !self.size.is_empty()
+    }
+}
+
+/// sha256 or stronger, as the wheel specification requires.
+pub(crate) fn hasher_for(algorithm: &str) -> Option<Box<dyn DynDigest>> {
+    match algorithm {
+        "sha256" => Some(Box::new(Sha256::default())),
+        "sha384" => Some(Box::new(Sha384::default())),
+        "sha512" => Some(Box::new(Sha512::default())),
+        _ => None,
+    }
+}
+
+/// Digest and length of everything written through it.
+pub(crate) struct HashingWriter<W> {
+    inner: W,
+    hasher: Box<dyn DynDigest>,
+    len: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self::with_hasher(inner, Box::new(Sha256::default()))
+    }
+
+    pub(crate) fn with_hasher(inner: W, hasher: Box<dyn DynDigest>) -> Self {
+        Self {
+            inner,
+            hasher,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn finish(self) -> (String, u64) {
+        let digest = self.hasher.finalize();
+        (
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest),
+            self.len,
+        )
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) fn record_line(name: &str, (hash, len): &(String, u64)) -> String {
+    format!("{},sha256={},{}", quote_record_path(name), hash, len)
+}
+
+pub(crate) fn quote_record_path(name: &str) -> String {
+    if name.contains(',') || name.contains('"') {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    }
+}
+
+pub(crate) fn unquote_record_path(field: &str) -> String {
+    match field
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => field.to_string(),
+    }
+}
@@ -0,0 +1,428 @@
+use super::archive::Changes;
+use super::discover::{collect_files, is_loadable_object, rel_string, SystemCandidate};
+use super::model::{metadata_header_values, remove_metadata_headers};
+use super::split::origin_path_to;
+use super::{
+    default_output_wheel, discover_candidates, Provenance, Session, SystemLibrary, WheelBuilder,
+    DEFAULT_LOCAL_VERSION, PROVENANCE_FILE,
+};
+use crate::cache::hash_file;
+use crate::elf::soname::soname_stem;
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::patch::{add_runpath, remove_runpath, replace_needed, set_soname};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Vendors system libraries back into a wheel. Created by [`crate::Unrepair::revendor`].
+pub struct RevendorBuilder {
+    wheel: PathBuf,
+    options: WheelBuilder,
+}
+
+/// Result of [`RevendorBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct RevendorResult {
+    pub input_wheel: String,
+    pub output_wheel: String,
+    /// False when `Requires-External` said what to vendor.
+    pub from_provenance: bool,
+    pub libraries: Vec<RevendoredLibrary>,
+    pub warnings: Vec<String>,
+}
+
+/// One system library copied into the wheel.
+#[derive(Debug, Serialize)]
+pub struct RevendoredLibrary {
+    /// SONAME of the system library.
+    pub soname: String,
+    pub system_path: String,
+    /// Path of the copy; its file name is also its SONAME.
+    pub path: String,
+    /// Objects whose `DT_NEEDED` entry now names the copy.
+    pub extensions: Vec<String>,
+}
+
+impl RevendorBuilder {
+    pub(crate) fn new(wheel: PathBuf) -> Self {
+        Self {
+            wheel,
+            options: WheelBuilder::default(),
+        }
+    }
+
+    /// Input, system library and output settings; checks are not used.
+    pub fn options(mut self, options: WheelBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(self) -> Result<RevendorResult> {
+        run(self)
+    }
+}
+
+/// Objects needing each SONAME, with the RUNPATH entry each got for it.
+type Targets = BTreeMap<String, Vec<(String, Option<String>)>>;
+
+fn run(revendor: RevendorBuilder) -> Result<RevendorResult> {
+    let args = &revendor.options;
+    let wheel = &revendor.wheel;
+    let mut session = Session::open(args, wheel, args.output_wheel.as_deref())?;
+    let layout = session.layout.clone();
+    let store = SummaryStore::new(args.cache.as_ref());
+
+    let provenance_name = format!("{}/{}", layout.dist_info, PROVENANCE_FILE);
+    let provenance = if session.archive.contains(&provenance_name) {
+        let text = session.archive.read_text(&provenance_name)?;
+        let provenance = serde_json::from_str::<Provenance>(&text)
+            .map_err(|err| Error::wheel(wheel, format!("{provenance_name}: {err}")))?;
+        Some(provenance)
+    } else {
+        None
+    };
+
+    let version = provenance
+        .as_ref()
+        .and_then(|provenance| provenance.original_version.clone())
+        .unwrap_or_else(|| {
+            without_local_label(&layout.name.version, args.label_or(DEFAULT_LOCAL_VERSION))
+        });
+    let tags = provenance
+        .as_ref()
+        .map(|provenance| provenance.original_tags.clone())
+        .unwrap_or_default();
+    session.output_layout = layout.restore(&version, &tags).relabel(&args.retag, None);
+    if args.output_wheel.is_none() {
+        session.output_wheel = default_output_wheel(wheel, &session.output_layout);
+    }
+
+    args.progress.stage("Finding libraries to vendor");
+    let metadata_name = format!("{}/METADATA", layout.dist_info);
+    let mut external = Vec::new();
+    let targets = match &provenance {
+        Some(provenance) => {
+            let mut targets = Targets::new();
+            for replacement in &provenance.replacements {
+                targets
+                    .entry(replacement.new_needed.clone())
+                    .or_default()
+                    .push((replacement.extension.clone(), replacement.runpath.clone()));
+            }
+            targets
+        }
+        None => {
+            let text = session.archive.read_text(&metadata_name)?;
+            external = metadata_header_values(&text, "Requires-External");
+            external_targets(&session.root, &external, &store)?
+        }
+    };
+    if targets.is_empty() {
+        return Err(Error::wheel(
+            wheel,
+            format!(
+                "nothing to vendor: no {PROVENANCE_FILE} replacements or Requires-External \
+                 libraries that an object in the wheel needs"
+            ),
+        ));
+    }
+
+    let systems = discover_candidates(args, &store)?;
+    let recorded = provenance
+        .iter()
+        .flat_map(|provenance| &provenance.system_libraries)
+        .map(|lib| (lib.soname.as_str(), lib))
+        .collect::<BTreeMap<_, _>>();
+    let libs_dir = provenance
+        .iter()
+        .flat_map(|provenance| &provenance.removed_bundled)
+        .filter_map(|path| path.rsplit_once('/').map(|(dir, _)| dir))
+        .find(|dir| dir.ends_with(".libs"))
+        .map(String::from)
+        .unwrap_or_else(|| format!("{}.libs", layout.name.distribution));
+
+    args.progress.stage("Vendoring system libraries");
+    // SONAME to (library file, where it came from, archive name of the copy,
+    // new SONAME). Dependencies of the copies that a system library given in
+    // the options provides are vendored too, as auditwheel does.
+    let mut copies = BTreeMap::new();
+    let mut pending = targets.keys().rev().cloned().collect::<Vec<_>>();
+    let mut unresolved = BTreeMap::<String, Vec<String>>::new();
+    while let Some(soname) = pending.pop() {
+        if copies.contains_key(&soname) {
+            continue;
+        }
+        let (system, origin) = find_system_library(
+            &soname,
+            recorded.get(soname.as_str()).copied(),
+            &systems,
+            &mut session.warnings,
+        )?;
+        for needed in &store.get(&system)?.needed {
+            if copies.contains_key(needed) || pending.contains(needed) || is_base_library(needed) {
+                continue;
+            }
+            if systems.iter().any(|system| &system.soname == needed) {
+                pending.push(needed.clone());
+            } else {
+                unresolved
+                    .entry(needed.clone())
+                    .or_default()
+                    .push(soname.clone());
+            }
+        }
+        let name = hashed_soname(&soname, &hash_file(&system)?);
+        let entry = format!("{libs_dir}/{name}");
+        if session.archive.contains(&entry) {
+            return Err(Error::wheel(
+                wheel,
+                format!("{entry} is already in the wheel"),
+            ));
+        }
+        let copy = session.root.join(&entry);
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent).map_err(Error::io(parent))?;
+        }
+        fs::copy(&system, &copy).map_err(Error::io(&copy))?;
+        set_soname(&copy, &copy, &name)?;
+        copies.insert(soname, (origin, entry, name));
+    }
+    for (needed, users) in unresolved {
+        if !copies.contains_key(&needed) {
+            session.warnings.push(format!(
+                "{needed} (needed by {}) was not vendored: no given system library has that \
+                 SONAME, so the target system must provide it",
+                users.join(", ")
+            ));
+        }
+    }
+
+    // Objects that now link to each copy.
+    let mut users = targets
+        .iter()
+        .map(|(soname, uses)| {
+            let objects = uses.iter().map(|(object, _)| object.clone()).collect();
+            (soname.clone(), objects)
+        })
+        .collect::<BTreeMap<_, Vec<_>>>();
+    // Copies that need each other find each other next to them.
+    for (_, entry, _) in copies.values() {
+        let copy = session.root.join(entry);
+        let needed = store.get(&copy)?.needed.clone();
+        let mut relinked = false;
+        for (soname, (_, _, name)) in &copies {
+            if needed.contains(soname) {
+                replace_needed(&copy, &copy, soname, name)?;
+                users.entry(soname.clone()).or_default().push(entry.clone());
+                relinked = true;
+            }
+        }
+        if relinked {
+            add_runpath(&copy, &copy, &["$ORIGIN".to_string()])?;
+        }
+        store.invalidate(&copy);
+    }
+
+    args.progress.stage("Relinking extensions");
+    let mut modified = BTreeSet::new();
+    for (soname, uses) in &targets {
+        let (_, _, name) = &copies[soname];
+        for (object, runpath) in uses {
+            let path = session.root.join(object);
+            if !path.is_file() {
+                return Err(Error::wheel(
+                    wheel,
+                    format!("{object} needs {soname} but is not a shared object in the wheel"),
+                ));
+            }
+            let Some((_, installed)) = layout
+                .locate(object)
+                .filter(|(scheme, _)| scheme.is_importable())
+            else {
+                return Err(Error::wheel(
+                    wheel,
+                    format!("{object} needs {soname} but is not installed to site-packages"),
+                ));
+            };
+            replace_needed(&path, &path, soname, name)?;
+            if let Some(runpath) = runpath {
+                remove_runpath(&path, &path, std::slice::from_ref(runpath))?;
+            }
+            add_runpath(&path, &path, &[origin_path_to(installed, &libs_dir)])?;
+            store.invalidate(&path);
+            modified.insert(object.clone());
+        }
+    }
+
+    args.progress.stage("Repacking wheel");
+    let mut changes = Changes {
+        modified: modified.into_iter().collect(),
+        ..Changes::default()
+    };
+    for (_, entry, _) in copies.values() {
+        let path = session.root.join(entry);
+        let contents = fs::read(&path).map_err(Error::io(&path))?;
+        changes.added.push((entry.clone(), contents));
+    }
+    let dropped_headers = match &provenance {
+        Some(provenance) => {
+            changes.removed.insert(provenance_name);
+            provenance.metadata_headers().to_vec()
+        }
+        None => vec![(
+            "Requires-External",
+            external
+                .into_iter()
+                .filter(|value| copies.contains_key(requirement_name(value)))
+                .collect(),
+        )],
+    };
+    session.update_metadata(&mut changes, |text| {
+        dropped_headers
+            .iter()
+            .fold(text.to_string(), |text, (key, values)| {
+                remove_metadata_headers(&text, key, values)
+            })
+    })?;
+    session.archive.rewrite(
+        &session.output_wheel,
+        &session.root,
+        &changes,
+        &session.output_layout,
+        session.mtime,
+    )?;
+
+    let libraries = copies
+        .into_iter()
+        .map(|(soname, (origin, entry, _))| RevendoredLibrary {
+            extensions: users.remove(&soname).unwrap_or_default(),
+            soname,
+            system_path: origin,
+            path: entry,
+        })
+        .collect();
+    Ok(RevendorResult {
+        input_wheel: wheel.display().to_string(),
+        output_wheel: session.output_wheel.display().to_string(),
+        from_provenance: provenance.is_some(),
+        libraries,
+        warnings: session.warnings,
+    })
+}
+
+fn external_targets(root: &Path, external: &[String], store: &SummaryStore<'_>) -> Result<Targets> {
+    let mut targets = Targets::new();
+    for rel in collect_files(root, false)? {
+        let path = root.join(&rel);
+        if !is_loadable_object(&path)? {
+            continue;
+        }
+        let needed = &store.get(&path)?.needed;
+        for value in external {
+            let soname = requirement_name(value);
+            if needed.iter().any(|name| name == soname) {
+                targets
+                    .entry(soname.to_string())
+                    .or_default()
+                    .push((rel_string(&rel), None));
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// The recorded file if unchanged, otherwise the candidate with that SONAME.
+fn find_system_library(
+    soname: &str,
+    recorded: Option<&SystemLibrary>,
+    systems: &[SystemCandidate],
+    warnings: &mut Vec<String>,
+) -> Result<(PathBuf, String)> {
+    if let Some(lib) = recorded.filter(|lib| lib.provider.is_none()) {
+        let path = Path::new(&lib.path);
+        if path.is_file() && hash_file(path)? == lib.sha256 {
+            return Ok((path.to_path_buf(), lib.path.clone()));
+        }
+    }
+    let provider = recorded.and_then(|lib| lib.provider.as_deref());
+    let Some(system) = systems.iter().find(|system| {
+        system.soname == soname && (provider.is_none() || system.provider.is_some())
+    }) else {
+        return Err(Error::InvalidArgument(match provider {
+            Some(requirement) => format!(
+                "{soname} was provided by {requirement}: pass it with --provider-wheel or \
+                 --provider-dist to vendor it"
+            ),
+            None => format!(
+                "no system library with SONAME {soname} found from --system-lib/--system-lib-dir"
+            ),
+        }));
+    };
+    if let Some(lib) = recorded {
+        if hash_file(&system.path)? != lib.sha256 {
+            warnings.push(format!(
+                "{soname}: vendored {}, not the library the wheel was unvendored against ({})",
+                system.describe(),
+                lib.path
+            ));
+        }
+    }
+    Ok((system.path.clone(), system.describe()))
+}
+
+/// Libraries every manylinux system provides, which are never vendored.
+const BASE_LIBRARIES: [&str; 20] = [
+    "libc.so.6",
+    "libm.so.6",
+    "libdl.so.2",
+    "librt.so.1",
+    "libpthread.so.0",
+    "libgcc_s.so.1",
+    "libstdc++.so.6",
+    "libnsl.so.1",
+    "libutil.so.1",
+    "libresolv.so.2",
+    "libcrypt.so.1",
+    "libX11.so.6",
+    "libXext.so.6",
+    "libXrender.so.1",
+    "libICE.so.6",
+    "libSM.so.6",
+    "libGL.so.1",
+    "libgobject-2.0.so.0",
+    "libgthread-2.0.so.0",
+    "libglib-2.0.so.0",
+];
+
+fn is_base_library(soname: &str) -> bool {
+    BASE_LIBRARIES.contains(&soname)
+        || soname.starts_with("ld-linux")
+        || soname.starts_with("ld64.so")
+}
+
+/// auditwheel's name for a vendored copy, as in `libz-a1b2c3d4.so.1`.
+fn hashed_soname(soname: &str, sha256: &str) -> String {
+    let stem = soname_stem(soname).unwrap_or_else(|| soname.to_string());
+    format!("{stem}-{}{}", &sha256[..8], &soname[stem.len()..])
+}
+
+fn requirement_name(value: &str) -> &str {
+    value
+        .split(|c: char| c.is_whitespace() || matches!(c, '(' | ';'))
+        .next()
+        .unwrap_or(value)
+}
+
+/// `version` without a trailing `label` local version segment.
+fn without_local_label(version: &str, label: Option<&str>) -> String {
+    label
+        .and_then(|label| {
+            version
+                .strip_suffix(label)
+                .and_then(|rest| rest.strip_suffix(['+', '.']))
+        })
+        .unwrap_or(version)
+        .to_string()
+}
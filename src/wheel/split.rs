@@ -0,0 +1,331 @@
+use super::archive::{write_new_wheel, Changes};
+use super::discover::{
+    collect_files, discover_bundled_libs, is_loadable_object, rel_string, BundledLib,
+};
+use super::model::{add_metadata_headers, canonical};
+use super::{default_output_wheel, Session, WheelBuilder, WheelName};
+use crate::elf::store::SummaryStore;
+use crate::error::{Error, Result};
+use crate::patch::add_runpath;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SPLIT_LOCAL_VERSION: &str = "split";
+
+/// Moves bundled libraries into a generated wheel. Created by [`crate::Unrepair::split`].
+pub struct SplitBuilder {
+    wheel: PathBuf,
+    libs: Vec<String>,
+    libs_name: Option<String>,
+    output_dir: Option<PathBuf>,
+    options: WheelBuilder,
+}
+
+/// Result of [`SplitBuilder::run`].
+#[derive(Debug, Serialize)]
+pub struct SplitResult {
+    pub input_wheel: String,
+    pub output_wheel: String,
+    /// The generated wheel holding the moved libraries.
+    pub libs_wheel: String,
+    /// Distribution name of the generated wheel, required by the output.
+    pub libs_distribution: String,
+    /// Paths inside the input wheel of the libraries that were moved.
+    pub moved: Vec<String>,
+    /// Objects whose RUNPATH now points into the generated wheel.
+    pub relinked: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl SplitBuilder {
+    pub(crate) fn new(wheel: PathBuf) -> Self {
+        Self {
+            wheel,
+            libs: Vec::new(),
+            libs_name: None,
+            output_dir: None,
+            options: WheelBuilder::default(),
+        }
+    }
+
+    /// SONAME, file name or wheel path of a library to move (default: all).
+    pub fn lib(mut self, name: impl Into<String>) -> Self {
+        self.libs.push(name.into());
+        self
+    }
+
+    /// Distribution name of the generated wheel (default: `{name}-libs`).
+    pub fn libs_name(mut self, name: impl Into<String>) -> Self {
+        self.libs_name = Some(name.into());
+        self
+    }
+
+    /// Write both wheels into `dir` (default: next to the output wheel).
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Input and output settings; the default label is `split`.
+    pub fn options(mut self, options: WheelBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn run(self) -> Result<SplitResult> {
+        run(self)
+    }
+}
+
+fn run(split: SplitBuilder) -> Result<SplitResult> {
+    let args = &split.options;
+    let mut session = Session::open(args, &split.wheel, args.output_wheel.as_deref())?;
+    let layout = session.layout.clone();
+    session.output_layout = layout.relabel(&args.retag, args.label_or(SPLIT_LOCAL_VERSION));
+    if args.output_wheel.is_none() {
+        session.output_wheel = default_output_wheel(&split.wheel, &session.output_layout);
+    }
+    if let Some(dir) = &split.output_dir {
+        let name = session
+            .output_wheel
+            .file_name()
+            .expect("default output wheel has a file name");
+        session.output_wheel = dir.join(name);
+    }
+    let libs_name = split
+        .libs_name
+        .clone()
+        .unwrap_or_else(|| format!("{}-libs", layout.name.distribution));
+    if !is_valid_distribution_name(&libs_name) {
+        return Err(Error::InvalidArgument(format!(
+            "invalid distribution name '{libs_name}'"
+        )));
+    }
+    let package = canonical(&libs_name);
+
+    args.progress.stage("Selecting libraries to move");
+    let store = SummaryStore::new(args.cache.as_ref());
+    let bundled = discover_bundled_libs(&session.root, &layout, &store)?;
+    let (moved, warnings) = select(&bundled, &split.libs, &store)?;
+    let mut by_soname = BTreeMap::new();
+    for lib in &moved {
+        if let Some(other) = by_soname.insert(lib.soname.as_str(), lib) {
+            return Err(Error::wheel(
+                &split.wheel,
+                format!(
+                    "{} and {} both have SONAME {}",
+                    rel_string(&other.rel_path),
+                    rel_string(&lib.rel_path),
+                    lib.soname
+                ),
+            ));
+        }
+    }
+    session.warnings.extend(warnings);
+
+    args.progress.stage("Relinking extensions");
+    let moved_paths = moved
+        .iter()
+        .map(|lib| lib.rel_path.clone())
+        .collect::<BTreeSet<_>>();
+    let mut relinked = Vec::new();
+    for rel in collect_files(&session.root, false)? {
+        let name = rel_string(&rel);
+        let path = session.root.join(&rel);
+        if !is_loadable_object(&path)? {
+            continue;
+        }
+        if !store
+            .get(&path)?
+            .needed
+            .iter()
+            .any(|needed| by_soname.contains_key(needed.as_str()))
+        {
+            continue;
+        }
+        if moved_paths.contains(&rel) {
+            // Moved libraries end up next to each other.
+            add_runpath(&path, &path, &["$ORIGIN".to_string()])?;
+            store.invalidate(&path);
+            continue;
+        }
+        let Some(installed) = layout
+            .locate(&name)
+            .filter(|(scheme, _)| scheme.is_importable())
+            .map(|(_, inner)| inner)
+        else {
+            session.warnings.push(format!(
+                "{name} needs a moved library but is not installed to site-packages; \
+                 its search path was not changed"
+            ));
+            continue;
+        };
+        add_runpath(&path, &path, &[origin_path_to(installed, &package)])?;
+        store.invalidate(&path);
+        relinked.push(name);
+    }
+
+    args.progress.stage("Writing library wheel");
+    let output_dir = session
+        .output_wheel
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let libs_wheel_name = WheelName {
+        distribution: package.clone(),
+        version: layout.name.version.clone(),
+        build: None,
+        python_tag: "py3".to_string(),
+        abi_tag: "none".to_string(),
+        platform_tag: session.output_layout.name.platform_tag.clone(),
+    };
+    let libs_wheel = output_dir.join(libs_wheel_name.to_string());
+    let dist_info = format!("{}-{}.dist-info", package, layout.name.version);
+    let mut entries = vec![(format!("{package}/__init__.py"), Vec::new())];
+    for (soname, lib) in &by_soname {
+        let contents = fs::read(&lib.abs_path).map_err(Error::io(&lib.abs_path))?;
+        entries.push((format!("{package}/{soname}"), contents));
+    }
+    entries.push((
+        format!("{dist_info}/METADATA"),
+        format!(
+            "Metadata-Version: 2.1\nName: {libs_name}\nVersion: {}\n\
+             Summary: Shared libraries split out of {} {}\n",
+            layout.name.version, layout.name.distribution, layout.name.version
+        )
+        .into_bytes(),
+    ));
+    let mut wheel_file = format!(
+        "Wheel-Version: 1.0\nGenerator: unrepair {}\nRoot-Is-Purelib: false\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    for tag in libs_wheel_name.tags() {
+        wheel_file.push_str(&format!("Tag: {tag}\n"));
+    }
+    entries.push((format!("{dist_info}/WHEEL"), wheel_file.into_bytes()));
+    write_new_wheel(
+        &libs_wheel,
+        &entries,
+        &format!("{dist_info}/RECORD"),
+        session.mtime,
+    )?;
+
+    args.progress.stage("Repacking wheel");
+    let moved = moved_paths
+        .iter()
+        .map(|rel| rel_string(rel))
+        .collect::<Vec<_>>();
+    let mut changes = Changes {
+        modified: relinked.iter().cloned().collect(),
+        removed: moved.iter().cloned().collect(),
+        ..Changes::default()
+    };
+    let requirement = format!("{libs_name}=={}", layout.name.version);
+    session.update_metadata(&mut changes, |text| {
+        add_metadata_headers(text, "Requires-Dist", &[requirement])
+    })?;
+    session.archive.rewrite(
+        &session.output_wheel,
+        &session.root,
+        &changes,
+        &session.output_layout,
+        session.mtime,
+    )?;
+
+    Ok(SplitResult {
+        input_wheel: split.wheel.display().to_string(),
+        output_wheel: session.output_wheel.display().to_string(),
+        libs_wheel: libs_wheel.display().to_string(),
+        libs_distribution: libs_name,
+        moved,
+        relinked,
+        warnings: session.warnings,
+    })
+}
+
+/// The selected libraries plus their bundled dependencies.
+fn select(
+    bundled: &[BundledLib],
+    selectors: &[String],
+    store: &SummaryStore<'_>,
+) -> Result<(Vec<BundledLib>, Vec<String>)> {
+    let mut selected = BTreeSet::new();
+    for selector in selectors {
+        let matches = bundled
+            .iter()
+            .enumerate()
+            .filter(|(_, lib)| {
+                lib.soname == *selector
+                    || rel_string(&lib.rel_path) == *selector
+                    || lib
+                        .rel_path
+                        .file_name()
+                        .is_some_and(|name| name == selector.as_str())
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "no bundled library matches '{selector}'"
+            )));
+        }
+        selected.extend(matches);
+    }
+    if selectors.is_empty() {
+        selected.extend(0..bundled.len());
+    }
+    if selected.is_empty() {
+        return Err(Error::InvalidArgument(
+            "the wheel has no bundled libraries to move".to_string(),
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let mut pending = selected.iter().copied().collect::<Vec<_>>();
+    while let Some(index) = pending.pop() {
+        let needed = store.get(&bundled[index].abs_path)?.needed.clone();
+        for (dep, lib) in bundled.iter().enumerate() {
+            if needed.contains(&lib.soname) && selected.insert(dep) {
+                warnings.push(format!(
+                    "Also moved {}: needed by {}",
+                    rel_string(&lib.rel_path),
+                    bundled[index].soname
+                ));
+                pending.push(dep);
+            }
+        }
+    }
+    Ok((
+        selected
+            .into_iter()
+            .map(|index| bundled[index].clone())
+            .collect(),
+        warnings,
+    ))
+}
+
+/// `$ORIGIN`-relative path from `installed` to `dir`, both in site-packages.
+pub(super) fn origin_path_to(installed: &str, dir: &str) -> String {
+    let mut from = installed.split('/').collect::<Vec<_>>();
+    from.pop();
+    let to = dir
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec!["$ORIGIN"];
+    parts.extend(std::iter::repeat_n("..", from.len() - common));
+    parts.extend(&to[common..]);
+    parts.join("/")
+}
+
+fn is_valid_distribution_name(name: &str) -> bool {
+    let ends_ok = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    ends_ok(name.chars().next())
+        && ends_ok(name.chars().last())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
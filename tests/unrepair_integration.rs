@@ -3,6 +3,7 @@
 use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -110,6 +111,114 @@ fn parse_verneed_libraries(path: &Path) -> HashSet<String> {
         .collect()
 }
 
+fn write_wheel(path: &Path, entries: &[(&str, Vec<u8>)]) {
+    use std::io::Write;
+
+    let file = fs::File::create(path).expect("failed to create wheel");
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default();
+    for (name, data) in entries {
+        zip.start_file(*name, options)
+            .expect("failed to start entry");
+        zip.write_all(data).expect("failed to write entry");
+    }
+    zip.finish().expect("failed to finish wheel");
+}
+
+/// A sha256 RECORD line for `data`, as a wheel builder would write it.
+fn record_line(name: &str, data: &[u8]) -> String {
+    use base64::Engine as _;
+    use sha2::{Digest, Sha256};
+
+    let hash = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(data));
+    format!("{},sha256={},{}\n", name, hash, data.len())
+}
+
+/// Append a RECORD entry listing every other entry.
+fn with_record<'a>(
+    mut entries: Vec<(&'a str, Vec<u8>)>,
+    record_name: &'a str,
+) -> Vec<(&'a str, Vec<u8>)> {
+    let mut record = entries
+        .iter()
+        .map(|(name, data)| record_line(name, data))
+        .collect::<String>();
+    record.push_str(&format!("{},,\n", record_name));
+    entries.push((record_name, record.into_bytes()));
+    entries
+}
+
+/// Entry names in archive order.
+fn read_wheel_entries(path: &Path) -> Vec<String> {
+    let file = fs::File::open(path).expect("failed to open wheel");
+    let mut archive = zip::ZipArchive::new(file).expect("failed to read wheel");
+    (0..archive.len())
+        .map(|i| archive.by_index(i).expect("bad entry").name().to_owned())
+        .collect()
+}
+
+fn read_wheel_entry(path: &Path, name: &str) -> Vec<u8> {
+    use std::io::Read;
+
+    let file = fs::File::open(path).expect("failed to open wheel");
+    let mut archive = zip::ZipArchive::new(file).expect("failed to read wheel");
+    let mut entry = archive.by_name(name).expect("missing wheel entry");
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).expect("failed to read entry");
+    data
+}
+
+/// Build `mypkg-1.0-cp312-cp312-linux_x86_64.whl` containing one extension
+/// linked against a vendored `libbundled.so`, plus a compatible system
+/// `libbundled.so.2` outside the wheel.
+fn build_wheel_case(temp: &TempDir) -> (PathBuf, PathBuf) {
+    let (ext, bundled, _system) = build_case(
+        temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libbundled.so.2",
+        None,
+        None,
+    );
+    let system_dir = temp.path().join("system");
+    fs::create_dir_all(&system_dir).expect("failed to create system dir");
+    fs::copy(
+        temp.path().join("libsystem.so"),
+        system_dir.join("libbundled.so.2"),
+    )
+    .expect("failed to copy system library");
+
+    let wheel = temp.path().join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = vec![
+        ("mypkg/__init__.py", b"".to_vec()),
+        ("mypkg/_ext.so", fs::read(&ext).expect("read extension")),
+        (
+            "mypkg.libs/libbundled.so",
+            fs::read(&bundled).expect("read bundled"),
+        ),
+        (
+            "mypkg-1.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0\n".to_vec(),
+        ),
+        (
+            "mypkg-1.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+    (wheel, system_dir)
+}
+
 fn basename_no_lib_prefix(name: &str) -> String {
     let stem = name.strip_suffix(".so").unwrap_or(name);
     stem.strip_prefix("lib").unwrap_or(stem).to_string()
@@ -724,3 +833,1872 @@ fn cli_patch_is_skipped_when_validation_fails() {
         "patch output should not be created when verdict is incompatible"
     );
 }
+
+#[test]
+fn cli_check_infers_bundled_library_from_runpath() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libbundled.so.2",
+        None,
+        None,
+    );
+
+    // WHEN
+    let inferred =
+        unrepair::infer::infer_bundled(&ext, &system).expect("bundled library should be inferred");
+    let output = run_output(
+        Command::new(unrepair_bin())
+            .arg("check")
+            .arg("--extension")
+            .arg(&ext)
+            .arg("--system")
+            .arg(&system),
+    );
+
+    // THEN
+    assert_eq!(inferred.needed, "libbundled.so");
+    assert_eq!(
+        fs::canonicalize(&inferred.path).expect("inferred path should exist"),
+        fs::canonicalize(&bundled).expect("bundled path should exist")
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "command failed:\nstderr:\n{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("Inferred bundled library") && stderr.contains("libbundled.so"),
+        "stderr did not report the inferred library:\n{}",
+        stderr
+    );
+}
+
+struct BannedSymbolCheck;
+
+const BANNED_SYMBOL: unrepair::codes::Code = unrepair::codes::Code::new("X100", "banned-symbol");
+
+impl unrepair::check::Check for BannedSymbolCheck {
+    fn name(&self) -> &str {
+        "banned"
+    }
+
+    fn codes(&self) -> Vec<unrepair::codes::Code> {
+        vec![BANNED_SYMBOL]
+    }
+
+    fn run(
+        &self,
+        ctx: &unrepair::check::CheckContext<'_>,
+    ) -> anyhow::Result<Vec<unrepair::Diagnostic>> {
+        Ok(ctx
+            .used_symbols
+            .iter()
+            .filter(|sym| sym.as_str() == "multiply")
+            .map(|sym| unrepair::Diagnostic {
+                code: BANNED_SYMBOL,
+                severity: unrepair::Severity::Error,
+                layer: unrepair::Layer::Custom,
+                symbol: Some(sym.clone()),
+                message: format!("Symbol '{}' is banned", sym),
+                suppressed: false,
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn custom_checks_can_be_registered_and_selected() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "librenamed-system.so",
+        None,
+        None,
+    );
+    let mut registry = unrepair::check::CheckRegistry::builtin();
+    registry
+        .register(BannedSymbolCheck)
+        .expect("custom check should register");
+
+    // WHEN
+    let all = unrepair::check_compatibility_with(&ext, &bundled, &system, &registry)
+        .expect("compatibility failed");
+    let only_elf = unrepair::check_compatibility_with(
+        &ext,
+        &bundled,
+        &system,
+        &registry.select(&["elf"]).expect("elf check should exist"),
+    )
+    .expect("compatibility failed");
+
+    // THEN
+    assert_eq!(all.verdict, Verdict::Incompatible);
+    assert!(all.diagnostics.iter().any(|d| {
+        d.layer == unrepair::Layer::Custom && d.symbol.as_deref() == Some("multiply")
+    }));
+    assert!(all
+        .diagnostics
+        .iter()
+        .any(|d| d.message.contains("SONAME mismatch")));
+    assert_eq!(only_elf.verdict, Verdict::Compatible);
+    assert!(
+        only_elf.diagnostics.is_empty(),
+        "unexpected diagnostics: {:?}",
+        only_elf.diagnostics
+    );
+}
+
+#[test]
+fn policy_rejects_unknown_codes_unless_a_check_registers_them() {
+    // GIVEN
+    let mut registry = unrepair::check::CheckRegistry::builtin();
+    registry
+        .register(BannedSymbolCheck)
+        .expect("custom check should register");
+    let mut policy = unrepair::policy::DiagnosticPolicy::new();
+
+    // WHEN
+    let typo = unrepair::policy::Rule::parse(unrepair::policy::Level::Allow, "W01O");
+    let before = policy
+        .extend_from_specs(
+            unrepair::policy::Level::Allow,
+            &["banned-symbol".to_string()],
+        )
+        .is_ok();
+    for code in registry.codes() {
+        policy.register_code(code);
+    }
+    let after = policy
+        .extend_from_specs(unrepair::policy::Level::Allow, &["X100:mult*".to_string()])
+        .is_ok();
+
+    // THEN
+    let err = typo.expect_err("typo should be rejected").to_string();
+    assert!(err.contains("unknown diagnostic code 'W01O'"), "{err}");
+    assert!(!before);
+    assert!(after);
+}
+
+#[test]
+fn policy_suppresses_and_promotes_diagnostics_by_code() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "librenamed-system.so",
+        None,
+        None,
+    );
+    let mut policy = unrepair::policy::DiagnosticPolicy::new();
+    policy
+        .extend_from_specs(unrepair::policy::Level::Allow, &["E001:mult*".to_string()])
+        .expect("valid allow rule")
+        .extend_from_specs(
+            unrepair::policy::Level::Deny,
+            &["soname-mismatch".to_string()],
+        )
+        .expect("valid deny rule");
+    let relaxed = unrepair::check::CheckRegistry::builtin()
+        .select(&["elf"])
+        .expect("elf check should exist")
+        .with_policy(policy.clone());
+    let strict_soname = unrepair::check::CheckRegistry::builtin().with_policy(policy);
+
+    // WHEN
+    let relaxed_result = unrepair::check_compatibility_with(&ext, &bundled, &system, &relaxed)
+        .expect("compatibility failed");
+    let strict_result = unrepair::check_compatibility_with(&ext, &bundled, &system, &strict_soname)
+        .expect("compatibility failed");
+
+    // THEN
+    assert_eq!(relaxed_result.verdict, Verdict::Compatible);
+    assert!(relaxed_result.diagnostics.iter().any(|d| {
+        d.code == unrepair::codes::MISSING_SYMBOL
+            && d.symbol.as_deref() == Some("multiply")
+            && d.suppressed
+    }));
+    assert_eq!(strict_result.verdict, Verdict::Incompatible);
+    assert!(strict_result.diagnostics.iter().any(|d| {
+        d.code == unrepair::codes::SONAME_MISMATCH && d.severity == unrepair::Severity::Error
+    }));
+}
+
+#[test]
+fn library_errors_are_typed() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, _bundled, _system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libsys.so",
+        None,
+        None,
+    );
+    let not_elf = temp.path().join("not-elf.so");
+    write_file(&not_elf, "definitely not an ELF file");
+    let patched = temp.path().join("ext.patched.so");
+
+    // WHEN
+    let needed_err = replace_needed(&ext, &patched, "libdoesnotexist.so", "libsys.so")
+        .expect_err("missing old DT_NEEDED should be rejected");
+    let parse_err = unrepair::elf::soname::extract_soname(&not_elf)
+        .expect_err("non-ELF input should be rejected");
+
+    // THEN
+    assert!(
+        matches!(
+            &needed_err,
+            unrepair::Error::NeededNotFound { path, needed }
+                if path == &ext && needed == "libdoesnotexist.so"
+        ),
+        "unexpected error: {needed_err:?}"
+    );
+    assert!(
+        matches!(&parse_err, unrepair::Error::ElfParse { path } if path == &not_elf),
+        "unexpected error: {parse_err:?}"
+    );
+    assert_eq!(parse_err.path(), Some(not_elf.as_path()));
+}
+
+#[derive(Clone, Default)]
+struct RecordingProgress(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+impl unrepair::progress::Progress for RecordingProgress {
+    fn stage(&self, name: &str) {
+        self.0.lock().unwrap().push(name.to_string());
+    }
+}
+
+#[test]
+fn wheel_builder_unvendors_and_reports_progress() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let output = temp.path().join("out.whl");
+    let progress = RecordingProgress::default();
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&output)
+        .progress(progress.clone())
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    assert_eq!(result.summary.patched_extensions, 1);
+    assert_eq!(
+        result.removed_bundled_paths,
+        vec!["mypkg.libs/libbundled.so".to_string()]
+    );
+    let entries = read_wheel_entries(&output);
+    assert!(!entries.iter().any(|e| e.ends_with("libbundled.so")));
+    let patched = temp.path().join("patched_ext.so");
+    fs::write(&patched, read_wheel_entry(&output, "mypkg/_ext.so")).expect("write ext");
+    assert!(parse_needed(&patched).contains("libbundled.so.2"));
+    assert!(progress
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|stage| stage == "Repacking wheel"));
+}
+
+#[test]
+fn in_memory_inputs_check_and_patch_without_paths() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libsys.so",
+        None,
+        None,
+    );
+    let ext_data = fs::read(&ext).expect("read extension");
+    let bundled_data = fs::read(&bundled).expect("read bundled");
+    let system_data = fs::read(&system).expect("read system");
+    let ext_bytes = unrepair::elf::ElfBytes::new("ext.so", &ext_data);
+
+    // WHEN
+    let result = unrepair::check_compatibility_bytes(
+        ext_bytes,
+        unrepair::elf::ElfBytes::new("libbundled.so", &bundled_data),
+        unrepair::elf::ElfBytes::new("libsys.so", &system_data),
+        &unrepair::check::CheckRegistry::builtin(),
+    )
+    .expect("compatibility failed");
+    let system_soname = unrepair::elf::soname::extract_soname_bytes(unrepair::elf::ElfBytes::new(
+        "libsys.so",
+        &system_data,
+    ))
+    .expect("soname should parse");
+    let patched = unrepair::patch::replace_needed_bytes(ext_bytes, "libbundled.so", "libsys.so")
+        .expect("patch should succeed");
+
+    // THEN
+    assert_eq!(result.verdict, Verdict::Incompatible);
+    assert!(result
+        .diagnostics
+        .iter()
+        .any(|d| d.symbol.as_deref() == Some("multiply")));
+    assert_eq!(system_soname.as_deref(), Some("libsys.so"));
+    let patched_path = temp.path().join("ext.patched.so");
+    fs::write(&patched_path, &patched).expect("write patched");
+    let needed = parse_needed(&patched_path);
+    assert!(needed.contains("libsys.so"), "DT_NEEDED: {:?}", needed);
+    assert!(!needed.contains("libbundled.so"), "DT_NEEDED: {:?}", needed);
+}
+
+#[test]
+fn analysis_cache_reuses_summaries_and_check_results() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libbundled.so",
+        None,
+        None,
+    );
+    let cache_dir = temp.path().join("cache");
+    let checks = unrepair::check::CheckRegistry::builtin();
+
+    // WHEN
+    let cache = unrepair::cache::AnalysisCache::open(&cache_dir).expect("open cache");
+    let first = cache
+        .check(&ext, &bundled, &system, &checks)
+        .expect("first check failed");
+    let reopened = unrepair::cache::AnalysisCache::open(&cache_dir).expect("reopen cache");
+    let second = reopened
+        .check(&ext, &bundled, &system, &checks)
+        .expect("second check failed");
+    let summary = reopened.summary(&system).expect("cached summary");
+
+    // THEN
+    assert_eq!(first.verdict, Verdict::Incompatible);
+    assert_eq!(second.verdict, first.verdict);
+    assert_eq!(second.diagnostics.len(), first.diagnostics.len());
+    assert_eq!(summary.soname.as_deref(), Some("libbundled.so"));
+    let cached_files = walk_files(&cache_dir);
+    assert!(
+        cached_files.iter().any(|p| p.contains("/checks/")),
+        "no memoized check results in {:?}",
+        cached_files
+    );
+    assert!(cached_files.iter().any(|p| p.contains("/elf/")));
+}
+
+fn walk_files(root: &Path) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in fs::read_dir(&dir).expect("read dir") {
+            let path = entry.expect("dir entry").path();
+            if path.is_dir() {
+                stack.push(path);
+            } else {
+                out.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn parallel_wheel_run_matches_sequential_run() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let run = |jobs: usize| {
+        let output = temp.path().join(format!("out-{jobs}.whl"));
+        let result = unrepair::Unrepair::wheel(&wheel)
+            .system_lib_dir(&system_dir)
+            .output_wheel(&output)
+            .jobs(jobs)
+            .run()
+            .expect("wheel workflow failed");
+        (result, read_wheel_entries(&output))
+    };
+
+    // WHEN
+    let (sequential, sequential_entries) = run(1);
+    let (parallel, parallel_entries) = run(4);
+
+    // THEN
+    assert!(parallel.is_complete(), "failures: {:?}", parallel.failures);
+    assert_eq!(
+        parallel.summary.patched_extensions,
+        sequential.summary.patched_extensions
+    );
+    assert_eq!(
+        parallel.removed_bundled_paths,
+        sequential.removed_bundled_paths
+    );
+    assert_eq!(parallel_entries, sequential_entries);
+}
+
+/// RECORD line for `mypkg/data.bin` containing `data`, hashed with sha512.
+const DATA_BIN_SHA512: &str = "mypkg/data.bin,sha512=d8fOml2GuzhtRDu5Y5D6oSBjMVhpnIhEwwsTqwv5J2C35EFq6jl9uRtKwOXdVrjvfksGYWKrH9wIgxnObe_Idg,4\n";
+
+#[test]
+fn wheel_rewrite_copies_untouched_entries_raw() {
+    use base64::Engine as _;
+    use sha2::{Digest, Sha256};
+    use std::io::Write;
+
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let stored_dir = temp.path().join("stored");
+    fs::create_dir_all(&stored_dir).expect("failed to create dir");
+    let wheel = stored_dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    {
+        let file = fs::File::create(&wheel).expect("failed to create wheel");
+        let mut zip = zip::ZipWriter::new(file);
+        let deflated = zip::write::FileOptions::default();
+        let stored = deflated.compression_method(zip::CompressionMethod::Stored);
+        for name in read_wheel_entries(&case_wheel) {
+            let data = if name.ends_with("RECORD") {
+                // A sha512 line proves it is carried over rather than
+                // recomputed, which would give sha256.
+                let mut record = read_wheel_entry(&case_wheel, &name);
+                record.extend_from_slice(DATA_BIN_SHA512.as_bytes());
+                record
+            } else {
+                read_wheel_entry(&case_wheel, &name)
+            };
+            zip.start_file(name, deflated).expect("start entry");
+            zip.write_all(&data).expect("write entry");
+        }
+        zip.start_file("mypkg/data.bin", stored)
+            .expect("start entry");
+        zip.write_all(b"data").expect("write entry");
+        zip.finish().expect("finish wheel");
+    }
+    let output = temp.path().join("out.whl");
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&output)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    let file = fs::File::open(&output).expect("open output");
+    let mut archive = zip::ZipArchive::new(file).expect("read output");
+    assert_eq!(
+        archive
+            .by_name("mypkg/data.bin")
+            .expect("data entry")
+            .compression(),
+        zip::CompressionMethod::Stored
+    );
+    drop(archive);
+
+    let record = String::from_utf8(read_wheel_entry(
+        &output,
+        "mypkg-1.0+unrepaired.dist-info/RECORD",
+    ))
+    .expect("RECORD is utf-8");
+    assert!(record.contains(DATA_BIN_SHA512));
+    let ext = read_wheel_entry(&output, "mypkg/_ext.so");
+    let hash = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(&ext));
+    assert!(record.contains(&format!("mypkg/_ext.so,sha256={},{}\n", hash, ext.len())));
+    assert!(!record.contains("libbundled.so,"));
+    assert!(record.ends_with("mypkg-1.0+unrepaired.dist-info/RECORD,,\n"));
+}
+
+#[test]
+fn wheel_output_is_reproducible_and_keeps_entry_metadata() {
+    use std::io::Write;
+
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let input_dir = temp.path().join("input");
+    fs::create_dir_all(&input_dir).expect("failed to create dir");
+    let wheel = input_dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    {
+        let file = fs::File::create(&wheel).expect("failed to create wheel");
+        let mut zip = zip::ZipWriter::new(file);
+        let mut names = read_wheel_entries(&case_wheel);
+        // Put dist-info first so the output has to move it to the end.
+        names.sort_by_key(|name| !name.contains(".dist-info/"));
+        for name in names {
+            let mode = if name.ends_with(".so") { 0o755 } else { 0o644 };
+            let options = zip::write::FileOptions::default().unix_permissions(mode);
+            let mut data = read_wheel_entry(&case_wheel, &name);
+            if name.ends_with("RECORD") {
+                data.extend_from_slice(
+                    record_line("mypkg-1.0.data/scripts/tool", b"#!/bin/sh\n").as_bytes(),
+                );
+            }
+            zip.start_file(name.as_str(), options).expect("start entry");
+            zip.write_all(&data).expect("write entry");
+        }
+        let script = zip::write::FileOptions::default().unix_permissions(0o755);
+        zip.start_file("mypkg-1.0.data/scripts/tool", script)
+            .expect("start entry");
+        zip.write_all(b"#!/bin/sh\n").expect("write entry");
+        zip.finish().expect("finish wheel");
+    }
+    let run = |name: &str| {
+        let output = temp.path().join(name);
+        let result = unrepair::Unrepair::wheel(&wheel)
+            .system_lib_dir(&system_dir)
+            .output_wheel(&output)
+            .source_date_epoch(1_700_000_000)
+            .run()
+            .expect("wheel workflow failed");
+        assert!(result.is_complete(), "failures: {:?}", result.failures);
+        output
+    };
+
+    // WHEN
+    let first = run("first.whl");
+    let second = run("second.whl");
+
+    // THEN
+    assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+    assert_eq!(
+        read_wheel_entries(&first),
+        vec![
+            "mypkg/__init__.py",
+            "mypkg/_ext.so",
+            "mypkg-1.0+unrepaired.data/scripts/tool",
+            "mypkg-1.0+unrepaired.dist-info/METADATA",
+            "mypkg-1.0+unrepaired.dist-info/WHEEL",
+            "mypkg-1.0+unrepaired.dist-info/unrepair.json",
+            "mypkg-1.0+unrepaired.dist-info/RECORD",
+        ]
+    );
+    let file = fs::File::open(&first).expect("open output");
+    let mut archive = zip::ZipArchive::new(file).expect("read output");
+    let script = archive
+        .by_name("mypkg-1.0+unrepaired.data/scripts/tool")
+        .expect("script entry");
+    assert_eq!(script.unix_mode().map(|m| m & 0o777), Some(0o755));
+    drop(script);
+    let ext = archive.by_name("mypkg/_ext.so").expect("extension entry");
+    assert_eq!(ext.unix_mode().map(|m| m & 0o777), Some(0o755));
+    let modified = ext.last_modified();
+    assert_eq!(
+        (modified.year(), modified.month(), modified.day()),
+        (2023, 11, 14)
+    );
+    assert_eq!((modified.hour(), modified.minute()), (22, 13));
+}
+
+#[test]
+fn wheel_with_unsafe_entries_is_rejected() {
+    use std::io::Write;
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let record = ("pkg-1.0.dist-info/RECORD", b"".to_vec());
+    let traversal = temp.path().join("traversal.whl");
+    write_wheel(&traversal, &[("../evil.so", b"x".to_vec()), record.clone()]);
+    let symlink = temp.path().join("symlink.whl");
+    {
+        let file = fs::File::create(&symlink).expect("failed to create wheel");
+        let mut zip = zip::ZipWriter::new(file);
+        zip.add_symlink(
+            "pkg/link.so",
+            "/etc/passwd",
+            zip::write::FileOptions::default(),
+        )
+        .expect("add symlink");
+        zip.start_file(record.0, zip::write::FileOptions::default())
+            .expect("start entry");
+        zip.write_all(&record.1).expect("write entry");
+        zip.finish().expect("finish wheel");
+    }
+    let bomb = temp.path().join("bomb.whl");
+    write_wheel(&bomb, &[("pkg/zeros.bin", vec![0u8; 4 << 20]), record]);
+
+    // WHEN
+    let run = |wheel: &Path| {
+        unrepair::Unrepair::wheel(wheel)
+            .system_lib_dir(temp.path())
+            .output_wheel(temp.path().join("out.whl"))
+            .limits(unrepair::wheel::ArchiveLimits {
+                max_ratio: 100,
+                ..Default::default()
+            })
+            .run()
+            .expect_err("unsafe wheel was accepted")
+    };
+    let errors = [run(&traversal), run(&symlink), run(&bomb)];
+
+    // THEN
+    let entries = errors
+        .iter()
+        .map(|err| match err {
+            unrepair::Error::UnsafeEntry { entry, .. } => entry.as_str(),
+            other => panic!("unexpected error: {other}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(entries, ["../evil.so", "pkg/link.so", "pkg/zeros.bin"]);
+    assert!(!temp.path().join("evil.so").exists());
+}
+
+/// Rewrite the uncompressed size stored for every entry of `actual` bytes to
+/// `declared`, in both the local and the central directory headers.
+fn understate_entry_sizes(wheel: &Path, actual: u32, declared: u32) {
+    let mut bytes = fs::read(wheel).expect("read wheel");
+    for pos in 0..bytes.len().saturating_sub(4) {
+        let size_at = match &bytes[pos..pos + 4] {
+            b"PK\x03\x04" => pos + 22,
+            b"PK\x01\x02" => pos + 24,
+            _ => continue,
+        };
+        if bytes.get(size_at..size_at + 4) == Some(&actual.to_le_bytes()[..]) {
+            bytes[size_at..size_at + 4].copy_from_slice(&declared.to_le_bytes());
+        }
+    }
+    fs::write(wheel, bytes).expect("write wheel");
+}
+
+#[test]
+fn wheel_entries_that_decompress_past_their_declared_size_are_rejected() {
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let actual = 4u32 << 20;
+    let zeros = ("mypkg/zeros.bin", vec![0u8; actual as usize]);
+    let metadata = (
+        "mypkg-1.0.dist-info/METADATA",
+        b"Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0\n".to_vec(),
+    );
+    let wheel_file = (
+        "mypkg-1.0.dist-info/WHEEL",
+        b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: py3-none-any\n"
+            .to_vec(),
+    );
+    let unlisted = temp
+        .path()
+        .join("unlisted")
+        .join("mypkg-1.0-py3-none-any.whl");
+    fs::create_dir_all(unlisted.parent().unwrap()).expect("failed to create dir");
+    let mut entries = with_record(
+        vec![metadata.clone(), wheel_file.clone()],
+        "mypkg-1.0.dist-info/RECORD",
+    );
+    entries.insert(0, zeros.clone());
+    write_wheel(&unlisted, &entries);
+    understate_entry_sizes(&unlisted, actual, 100);
+    let listed = temp
+        .path()
+        .join("listed")
+        .join("mypkg-1.0-py3-none-any.whl");
+    fs::create_dir_all(listed.parent().unwrap()).expect("failed to create dir");
+    write_wheel(
+        &listed,
+        &with_record(
+            vec![zeros, metadata, wheel_file],
+            "mypkg-1.0.dist-info/RECORD",
+        ),
+    );
+    understate_entry_sizes(&listed, actual, 100);
+
+    // WHEN
+    let run = |wheel: &Path| {
+        unrepair::Unrepair::wheel(wheel)
+            .prune_unused(true)
+            .ignore_record_errors(true)
+            .output_wheel(temp.path().join("out.whl"))
+            .limits(unrepair::wheel::ArchiveLimits {
+                max_entry_size: 1 << 20,
+                ..Default::default()
+            })
+            .run()
+            .expect_err("entry larger than declared was accepted")
+    };
+    let errors = [run(&unlisted), run(&listed)];
+
+    // THEN
+    for err in &errors {
+        match err {
+            unrepair::Error::UnsafeEntry { entry, reason, .. } => {
+                assert_eq!(entry, "mypkg/zeros.bin");
+                assert!(reason.contains("exceeds the limit"), "{reason}");
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+    assert!(!temp.path().join("out.whl").exists());
+}
+
+#[test]
+fn wheel_with_mismatched_record_is_rejected_unless_ignored() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let tampered_dir = temp.path().join("tampered");
+    fs::create_dir_all(&tampered_dir).expect("failed to create dir");
+    let wheel = tampered_dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = read_wheel_entries(&case_wheel)
+        .into_iter()
+        .map(|name| {
+            let mut data = read_wheel_entry(&case_wheel, &name);
+            if name == "mypkg/__init__.py" {
+                data.extend_from_slice(b"import os\n");
+            }
+            (name, data)
+        })
+        .chain([
+            ("mypkg/extra.py".to_string(), b"".to_vec()),
+            ("mypkg-1.0.dist-info/RECORD.jws".to_string(), b"{}".to_vec()),
+        ])
+        .collect::<Vec<_>>();
+    let borrowed = entries
+        .iter()
+        .map(|(name, data)| (name.as_str(), data.clone()))
+        .collect::<Vec<_>>();
+    write_wheel(&wheel, &borrowed);
+    let builder = || {
+        unrepair::Unrepair::wheel(&wheel)
+            .system_lib_dir(&system_dir)
+            .output_wheel(temp.path().join("out.whl"))
+    };
+
+    // WHEN
+    let err = builder().run().expect_err("tampered wheel was accepted");
+    let result = builder()
+        .ignore_record_errors(true)
+        .run()
+        .expect("ignored RECORD errors still failed");
+
+    // THEN
+    let unrepair::Error::RecordMismatch { problems, .. } = err else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(
+        problems,
+        [
+            "mypkg/__init__.py: sha256 hash does not match RECORD",
+            "mypkg/extra.py: not listed in RECORD",
+        ]
+    );
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    assert_eq!(result.warnings.len(), 3, "warnings: {:?}", result.warnings);
+    assert!(result.warnings[2].contains("RECORD.jws"));
+    let output = temp.path().join("out.whl");
+    assert!(!read_wheel_entries(&output)
+        .iter()
+        .any(|name| name.ends_with("RECORD.jws")));
+    let record = String::from_utf8(read_wheel_entry(
+        &output,
+        "mypkg-1.0+unrepaired.dist-info/RECORD",
+    ))
+    .expect("RECORD is utf-8");
+    assert!(record.contains(&record_line(
+        "mypkg/__init__.py",
+        &read_wheel_entry(&output, "mypkg/__init__.py")
+    )));
+}
+
+#[test]
+fn wheel_layout_follows_dist_info_and_data_schemes() {
+    require_build_tools();
+
+    // GIVEN
+    let name = unrepair::wheel::WheelName::parse("my_pkg-1.0-1-py2.py3-none-any.whl")
+        .expect("valid wheel name");
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let platlib_dir = temp.path().join("platlib");
+    fs::create_dir_all(&platlib_dir).expect("failed to create dir");
+    let wheel = platlib_dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = vec![
+        (
+            "mypkg-1.0.data/platlib/mypkg/_ext.so",
+            read_wheel_entry(&case_wheel, "mypkg/_ext.so"),
+        ),
+        (
+            "mypkg-1.0.data/platlib/mypkg.libs/libbundled.so",
+            read_wheel_entry(&case_wheel, "mypkg.libs/libbundled.so"),
+        ),
+        // Not an ELF file and not importable: must not be parsed.
+        (
+            "mypkg-1.0.data/data/share/mypkg/plugin.so",
+            b"not elf".to_vec(),
+        ),
+        (
+            "mypkg-1.0.dist-info/METADATA",
+            read_wheel_entry(&case_wheel, "mypkg-1.0.dist-info/METADATA"),
+        ),
+        (
+            "mypkg-1.0.dist-info/WHEEL",
+            read_wheel_entry(&case_wheel, "mypkg-1.0.dist-info/WHEEL"),
+        ),
+    ];
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+    let output = temp.path().join("out.whl");
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&output)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert_eq!(name.distribution, "my_pkg");
+    assert_eq!(name.build.as_deref(), Some("1"));
+    assert_eq!(name.tags(), ["py2-none-any", "py3-none-any"]);
+    assert_eq!(name.to_string(), "my_pkg-1.0-1-py2.py3-none-any.whl");
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    assert_eq!(result.summary.patched_extensions, 1);
+    assert_eq!(
+        result.removed_bundled_paths,
+        vec!["mypkg-1.0.data/platlib/mypkg.libs/libbundled.so".to_string()]
+    );
+    let entries = read_wheel_entries(&output);
+    assert!(entries
+        .iter()
+        .any(|name| name == "mypkg-1.0+unrepaired.data/data/share/mypkg/plugin.so"));
+}
+
+#[test]
+fn wheel_output_is_retagged_and_relabelled() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let dist_dir = temp.path().join("dist");
+    fs::create_dir_all(&dist_dir).expect("failed to create dir");
+    let wheel =
+        dist_dir.join("mypkg-1.0-cp312-cp312-manylinux_2_17_x86_64.manylinux2014_x86_64.whl");
+    let wheel_file = b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\n\
+Tag: cp312-cp312-manylinux_2_17_x86_64\nTag: cp312-cp312-manylinux2014_x86_64\n";
+    let names = read_wheel_entries(&case_wheel);
+    let entries = names
+        .iter()
+        .filter(|name| !name.ends_with("RECORD"))
+        .map(|name| {
+            let data = if name.ends_with("WHEEL") {
+                wheel_file.to_vec()
+            } else {
+                read_wheel_entry(&case_wheel, name)
+            };
+            (name.as_str(), data)
+        })
+        .collect::<Vec<_>>();
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .retag(unrepair::wheel::Retag::Linux)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    let output = dist_dir.join("mypkg-1.0+unrepaired-cp312-cp312-linux_x86_64.whl");
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    assert_eq!(result.output_wheel, output.display().to_string());
+    let wheel_out = read_wheel_entry(&output, "mypkg-1.0+unrepaired.dist-info/WHEEL");
+    assert_eq!(
+        String::from_utf8(wheel_out.clone()).unwrap(),
+        "Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+    );
+    let metadata = read_wheel_entry(&output, "mypkg-1.0+unrepaired.dist-info/METADATA");
+    assert!(String::from_utf8(metadata)
+        .unwrap()
+        .contains("Version: 1.0+unrepaired\n"));
+    let record = String::from_utf8(read_wheel_entry(
+        &output,
+        "mypkg-1.0+unrepaired.dist-info/RECORD",
+    ))
+    .unwrap();
+    assert!(record.contains(&record_line(
+        "mypkg-1.0+unrepaired.dist-info/WHEEL",
+        &wheel_out
+    )));
+    assert!(!record.contains("mypkg-1.0.dist-info/"));
+}
+
+#[test]
+fn wheel_output_records_provenance_and_requires_external() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let output = temp.path().join("out.whl");
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&output)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    let metadata = String::from_utf8(read_wheel_entry(
+        &output,
+        "mypkg-1.0+unrepaired.dist-info/METADATA",
+    ))
+    .unwrap();
+    assert_eq!(
+        metadata,
+        "Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0+unrepaired\n\
+Requires-External: libbundled.so.2\n"
+    );
+    let json = read_wheel_entry(&output, "mypkg-1.0+unrepaired.dist-info/unrepair.json");
+    let provenance: unrepair::wheel::Provenance =
+        serde_json::from_slice(&json).expect("unrepair.json parses");
+    assert_eq!(provenance.unrepair_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(provenance.removed_bundled, ["mypkg.libs/libbundled.so"]);
+    assert_eq!(
+        provenance.replacements,
+        [unrepair::wheel::NeededReplacement {
+            extension: "mypkg/_ext.so".to_string(),
+            old_needed: "libbundled.so".to_string(),
+            new_needed: "libbundled.so.2".to_string(),
+            runpath: None,
+        }]
+    );
+    let system_lib = system_dir.join("libbundled.so.2");
+    assert_eq!(provenance.system_libraries.len(), 1);
+    assert_eq!(provenance.system_libraries[0].soname, "libbundled.so.2");
+    assert_eq!(
+        provenance.system_libraries[0].sha256,
+        unrepair::cache::hash_file(&system_lib).unwrap()
+    );
+    assert_eq!(
+        provenance.system_libraries[0].build_id,
+        unrepair::elf::build_id::read_build_id(&system_lib).unwrap()
+    );
+    let record = String::from_utf8(read_wheel_entry(
+        &output,
+        "mypkg-1.0+unrepaired.dist-info/RECORD",
+    ))
+    .unwrap();
+    assert!(record.contains(&record_line(
+        "mypkg-1.0+unrepaired.dist-info/unrepair.json",
+        &json
+    )));
+}
+
+#[test]
+fn failed_consumer_holds_back_library_and_rolls_back_transaction() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (ext, bundled, _system) = build_case(
+        &temp,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "bundled"; }
+            int extra(void) { return 1; }
+        "#,
+        r#"
+            int add(int a, int b) { return a + b; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+        "libbundled.so",
+        "libbundled.so.2",
+        None,
+        None,
+    );
+    let other_c = temp.path().join("other.c");
+    write_file(
+        &other_c,
+        "extern int extra(void);\nint other_func(void) { return extra(); }\n",
+    );
+    let other = temp.path().join("other.so");
+    compile_extension(&other_c, &other, temp.path(), "bundled");
+    let system_dir = temp.path().join("system");
+    fs::create_dir_all(&system_dir).expect("failed to create system dir");
+    fs::copy(
+        temp.path().join("libsystem.so"),
+        system_dir.join("libbundled.so.2"),
+    )
+    .expect("failed to copy system library");
+    let wheel = temp.path().join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = vec![
+        ("mypkg/_ext.so", fs::read(&ext).expect("read extension")),
+        ("mypkg/_other.so", fs::read(&other).expect("read extension")),
+        (
+            "mypkg.libs/libbundled.so",
+            fs::read(&bundled).expect("read bundled"),
+        ),
+        (
+            "mypkg-1.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0\n".to_vec(),
+        ),
+        (
+            "mypkg-1.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+    let held_output = temp.path().join("held.whl");
+    let rolled_output = temp.path().join("rolled.whl");
+
+    // WHEN
+    let held = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&held_output)
+        .all_consumers(true)
+        .run()
+        .expect("wheel workflow failed");
+    let rolled = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&rolled_output)
+        .transactional(true)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert_eq!(held.failures.len(), 1, "failures: {:?}", held.failures);
+    assert_eq!(held.summary.patched_extensions, 0);
+    assert_eq!(held.summary.held_back_extensions, 1);
+    assert!(held.removed_bundled_paths.is_empty());
+    let kept = temp.path().join("kept_ext.so");
+    fs::write(&kept, read_wheel_entry(&held_output, "mypkg/_ext.so")).expect("write ext");
+    assert!(parse_needed(&kept).contains("libbundled.so"));
+    assert!(read_wheel_entries(&held_output)
+        .iter()
+        .any(|name| name == "mypkg.libs/libbundled.so"));
+
+    assert!(rolled.rolled_back);
+    assert!(rolled.hard_failure);
+    assert!(!rolled.is_complete());
+    assert_eq!(rolled.summary.patched_extensions, 0);
+    assert!(!rolled_output.exists());
+    assert!(!temp.path().join("rolled.whl.partial").exists());
+}
+
+#[test]
+fn wheel_plan_is_reviewable_and_applies_exactly() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let planned_output = temp.path().join("planned.whl");
+    let plan_path = temp.path().join("plan.json");
+
+    // WHEN
+    let plan = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&planned_output)
+        .plan()
+        .expect("planning failed");
+    plan.save(&plan_path).expect("save plan");
+    let loaded = unrepair::wheel::WheelPlan::load(&plan_path).expect("load plan");
+    let applied_output = temp.path().join("applied.whl");
+    let applied = unrepair::Unrepair::wheel(&wheel)
+        .output_wheel(&applied_output)
+        .apply(&loaded)
+        .expect("apply failed");
+    let run_output = temp.path().join("run.whl");
+    unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&run_output)
+        .run()
+        .expect("wheel workflow failed");
+    let tampered = temp
+        .path()
+        .join("tampered")
+        .join(wheel.file_name().unwrap());
+    fs::create_dir_all(tampered.parent().unwrap()).expect("failed to create dir");
+    let mut bytes = fs::read(&wheel).expect("read wheel");
+    bytes.push(0);
+    fs::write(&tampered, bytes).expect("write wheel");
+    let rejected = unrepair::Unrepair::wheel(&tampered)
+        .output_wheel(temp.path().join("rejected.whl"))
+        .apply(&loaded);
+    let mut stale = loaded.clone();
+    stale.unrepair_version = "0.0.0".to_string();
+    let outdated = unrepair::Unrepair::wheel(&wheel)
+        .output_wheel(temp.path().join("outdated.whl"))
+        .apply(&stale);
+
+    // THEN
+    assert!(!planned_output.exists());
+    assert_eq!(loaded, plan);
+    assert!(plan.is_complete());
+    assert_eq!(plan.checks.len(), 1);
+    assert_eq!(plan.checks[0].extension, "mypkg/_ext.so");
+    assert_eq!(plan.checks[0].verdict, Verdict::Compatible);
+    assert_eq!(plan.edits.len(), 1);
+    assert_eq!(plan.edits[0].extension, "mypkg/_ext.so");
+    assert_eq!(plan.edits[0].old_needed, "libbundled.so");
+    assert_eq!(plan.edits[0].new_needed, "libbundled.so.2");
+    assert_eq!(plan.remove, ["mypkg.libs/libbundled.so"]);
+
+    assert!(applied.is_complete(), "failures: {:?}", applied.failures);
+    assert_eq!(applied.summary.patched_extensions, 1);
+    assert_eq!(
+        fs::read(&applied_output).unwrap(),
+        fs::read(&run_output).unwrap()
+    );
+    assert!(matches!(rejected, Err(unrepair::Error::Wheel { .. })));
+    assert!(!temp.path().join("rejected.whl").exists());
+    assert!(matches!(outdated, Err(unrepair::Error::InvalidArgument(_))));
+    assert!(!temp.path().join("outdated.whl").exists());
+}
+
+#[test]
+fn wheel_batch_shares_discovery_and_flags_differing_outcomes() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let dist = temp.path().join("dist");
+    fs::create_dir_all(&dist).expect("failed to create dir");
+    fs::copy(
+        &case_wheel,
+        dist.join("mypkg-1.0-cp311-cp311-linux_x86_64.whl"),
+    )
+    .expect("copy wheel");
+    fs::copy(
+        &case_wheel,
+        dist.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl"),
+    )
+    .expect("copy wheel");
+    let names = read_wheel_entries(&case_wheel);
+    let entries = names
+        .iter()
+        .filter(|name| !name.ends_with("RECORD") && !name.contains(".libs/"))
+        .map(|name| (name.as_str(), read_wheel_entry(&case_wheel, name)))
+        .collect::<Vec<_>>();
+    write_wheel(
+        &dist.join("mypkg-1.0-cp39-cp39-linux_x86_64.whl"),
+        &with_record(entries, "mypkg-1.0.dist-info/RECORD"),
+    );
+    let out = temp.path().join("out");
+
+    // WHEN
+    let result = unrepair::Unrepair::wheels(Vec::<PathBuf>::new())
+        .wheels_dir(&dist)
+        .output_dir(&out)
+        .options(unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir))
+        .run()
+        .expect("batch failed");
+
+    // THEN
+    assert!(!result.is_complete());
+    let wheels = result
+        .wheels
+        .iter()
+        .map(|entry| (entry.wheel.rsplit('/').next().unwrap(), entry.differs))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        wheels,
+        [
+            ("mypkg-1.0-cp311-cp311-linux_x86_64.whl", false),
+            ("mypkg-1.0-cp312-cp312-linux_x86_64.whl", false),
+            ("mypkg-1.0-cp39-cp39-linux_x86_64.whl", true),
+        ]
+    );
+    assert_eq!(
+        result.common_outcome.unvendored.iter().collect::<Vec<_>>(),
+        ["libbundled.so.2"]
+    );
+    assert!(result.wheels[2].outcome.error);
+    assert!(result.wheels[2]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("no bundled libraries matched"));
+    assert!(out
+        .join("mypkg-1.0+unrepaired-cp311-cp311-linux_x86_64.whl")
+        .exists());
+    assert!(out
+        .join("mypkg-1.0+unrepaired-cp312-cp312-linux_x86_64.whl")
+        .exists());
+}
+
+#[test]
+fn wheel_batch_processes_each_wheel_once_and_rejects_colliding_outputs() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (case_wheel, system_dir) = build_wheel_case(&temp);
+    let name = case_wheel.file_name().unwrap();
+    let dist = temp.path().join("dist");
+    let other = temp.path().join("other");
+    for dir in [&dist, &other] {
+        fs::create_dir_all(dir).expect("failed to create dir");
+        fs::copy(&case_wheel, dir.join(name)).expect("copy wheel");
+    }
+    let out = temp.path().join("out");
+    let options = || unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir);
+
+    // WHEN
+    let once = unrepair::Unrepair::wheels(vec![dist.join(name)])
+        .wheels_dir(&dist)
+        .output_dir(&out)
+        .options(options())
+        .run()
+        .expect("batch failed");
+    let colliding = unrepair::Unrepair::wheels(Vec::<PathBuf>::new())
+        .wheels_dir(&dist)
+        .wheels_dir(&other)
+        .output_dir(temp.path().join("colliding"))
+        .options(options())
+        .run();
+
+    // THEN
+    assert_eq!(once.wheels.len(), 1);
+    assert!(once.is_complete());
+    let err = match colliding {
+        Err(unrepair::Error::InvalidArgument(message)) => message,
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(err.contains("would both be written to"), "{err}");
+    assert!(!temp.path().join("colliding").exists());
+}
+
+/// Unpack every entry of `wheel` into `site`, as an installer would.
+fn install_wheel(wheel: &Path, site: &Path) {
+    for name in read_wheel_entries(wheel) {
+        let path = site.join(&name);
+        fs::create_dir_all(path.parent().unwrap()).expect("failed to create install dir");
+        fs::write(&path, read_wheel_entry(wheel, &name)).expect("failed to install entry");
+    }
+}
+
+#[test]
+fn installed_distribution_is_unvendored_in_place() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let site = temp.path().join("site-packages");
+    install_wheel(&wheel, &site);
+    let ext = site.join("mypkg/_ext.so");
+    fs::set_permissions(&ext, fs::Permissions::from_mode(0o751)).unwrap();
+
+    // WHEN
+    let result = unrepair::Unrepair::installed(&site, "MyPkg")
+        .options(unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir))
+        .run()
+        .expect("installed workflow failed");
+
+    // THEN
+    assert!(result.is_complete(), "failures: {:?}", result.failures);
+    assert_eq!(result.dist_info, "mypkg-1.0.dist-info");
+    assert_eq!(result.removed_bundled_paths, ["mypkg.libs/libbundled.so"]);
+    assert!(!site.join("mypkg.libs").exists());
+    assert!(parse_needed(&ext).contains("libbundled.so.2"));
+    assert_eq!(
+        fs::metadata(&ext).unwrap().permissions().mode() & 0o777,
+        0o751
+    );
+
+    let record = fs::read_to_string(site.join("mypkg-1.0.dist-info/RECORD")).unwrap();
+    assert!(!record.contains("libbundled.so,"));
+    for name in [
+        "mypkg/_ext.so",
+        "mypkg-1.0.dist-info/METADATA",
+        "mypkg-1.0.dist-info/unrepair.json",
+    ] {
+        let data = fs::read(site.join(name)).unwrap();
+        assert!(
+            record.contains(&record_line(name, &data)),
+            "{name}: {record}"
+        );
+    }
+    assert!(record.ends_with("mypkg-1.0.dist-info/RECORD,,\n"));
+    let metadata = fs::read_to_string(site.join("mypkg-1.0.dist-info/METADATA")).unwrap();
+    assert!(metadata.contains("Version: 1.0\nRequires-External: libbundled.so.2\n"));
+}
+
+#[test]
+fn audit_env_groups_duplicates_and_unvendors_every_distribution() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let site = temp.path().join("site-packages");
+    install_wheel(&wheel, &site);
+    let bundled = read_wheel_entry(&wheel, "mypkg.libs/libbundled.so");
+    let other = vec![
+        ("otherpkg/_ext.so", read_wheel_entry(&wheel, "mypkg/_ext.so")),
+        ("otherpkg.libs/libbundled.so", bundled.clone()),
+        (
+            "otherpkg-2.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: otherpkg\nVersion: 2.0\n".to_vec(),
+        ),
+        (
+            "otherpkg-2.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    for (name, data) in with_record(other, "otherpkg-2.0.dist-info/RECORD") {
+        let path = site.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    // WHEN
+    let report = unrepair::Unrepair::audit_env(&site)
+        .run()
+        .expect("audit failed");
+
+    // THEN
+    assert_eq!(report.groups.len(), 1);
+    assert_eq!(report.groups[0].stem, "libbundled");
+    let owners = report.groups[0]
+        .copies
+        .iter()
+        .map(|copy| copy.dist_info.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(owners, ["mypkg-1.0.dist-info", "otherpkg-2.0.dist-info"]);
+    assert_eq!(report.duplicate_bytes, bundled.len() as u64);
+    assert!(report.distributions.is_empty());
+    assert!(site.join("otherpkg.libs/libbundled.so").exists());
+
+    // WHEN
+    let report = unrepair::Unrepair::audit_env(&site)
+        .unvendor(true)
+        .options(unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir))
+        .run()
+        .expect("audit with unvendoring failed");
+
+    // THEN
+    assert!(report.is_complete(), "{:?}", report.distributions);
+    assert_eq!(report.distributions.len(), 2);
+    for pkg in ["mypkg", "otherpkg"] {
+        assert!(!site.join(format!("{pkg}.libs")).exists());
+        assert!(parse_needed(&site.join(format!("{pkg}/_ext.so"))).contains("libbundled.so.2"));
+    }
+    let record = fs::read_to_string(site.join("otherpkg-2.0.dist-info/RECORD")).unwrap();
+    assert!(!record.contains("libbundled.so,"));
+}
+
+#[test]
+fn split_moves_bundled_libraries_into_a_library_wheel() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, _system_dir) = build_wheel_case(&temp);
+    let out_dir = temp.path().join("out");
+
+    // WHEN
+    let result = unrepair::Unrepair::split(&wheel)
+        .output_dir(&out_dir)
+        .run()
+        .expect("split failed");
+
+    // THEN
+    assert_eq!(result.moved, ["mypkg.libs/libbundled.so"]);
+    assert_eq!(result.relinked, ["mypkg/_ext.so"]);
+    assert_eq!(result.libs_distribution, "mypkg-libs");
+    let libs_wheel = out_dir.join("mypkg_libs-1.0-py3-none-linux_x86_64.whl");
+    assert_eq!(Path::new(&result.libs_wheel), libs_wheel);
+    assert_eq!(
+        read_wheel_entries(&libs_wheel),
+        [
+            "mypkg_libs/__init__.py",
+            "mypkg_libs/libbundled.so",
+            "mypkg_libs-1.0.dist-info/METADATA",
+            "mypkg_libs-1.0.dist-info/WHEEL",
+            "mypkg_libs-1.0.dist-info/RECORD",
+        ]
+    );
+    let lib = read_wheel_entry(&libs_wheel, "mypkg_libs/libbundled.so");
+    let record = String::from_utf8(read_wheel_entry(
+        &libs_wheel,
+        "mypkg_libs-1.0.dist-info/RECORD",
+    ))
+    .unwrap();
+    assert!(record.contains(&record_line("mypkg_libs/libbundled.so", &lib)));
+
+    let output = Path::new(&result.output_wheel);
+    assert_eq!(
+        output,
+        out_dir.join("mypkg-1.0+split-cp312-cp312-linux_x86_64.whl")
+    );
+    let entries = read_wheel_entries(output);
+    assert!(!entries.iter().any(|name| name.contains("libbundled")));
+    let metadata = String::from_utf8(read_wheel_entry(
+        output,
+        "mypkg-1.0+split.dist-info/METADATA",
+    ))
+    .unwrap();
+    assert!(metadata.contains("Requires-Dist: mypkg-libs==1.0\n"));
+    let ext = temp.path().join("split_ext.so");
+    fs::write(&ext, read_wheel_entry(output, "mypkg/_ext.so")).unwrap();
+    let binary = lief::elf::Binary::parse(&ext).expect("failed to parse ELF");
+    assert!(unrepair::elf::dynamic::extract_search_paths(&binary)
+        .contains(&"$ORIGIN/../mypkg_libs".to_string()));
+    assert!(parse_needed(&ext).contains("libbundled.so"));
+}
+
+#[test]
+fn provider_wheel_library_replaces_bundled_library() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let provider = temp.path().join("provider-1.0-py3-none-linux_x86_64.whl");
+    let entries = vec![
+        ("provider/__init__.py", b"".to_vec()),
+        (
+            "provider/lib/libbundled.so.2",
+            fs::read(system_dir.join("libbundled.so.2")).expect("read system library"),
+        ),
+        (
+            "provider-1.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: provider\nVersion: 1.0\n".to_vec(),
+        ),
+        (
+            "provider-1.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: py3-none-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    write_wheel(
+        &provider,
+        &with_record(entries, "provider-1.0.dist-info/RECORD"),
+    );
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .provider_wheel(&provider)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete());
+    assert_eq!(result.removed_bundled_paths, ["mypkg.libs/libbundled.so"]);
+    assert_eq!(
+        result.pairs[0].system_path,
+        format!("{}:provider/lib/libbundled.so.2", provider.display())
+    );
+    let output = Path::new(&result.output_wheel);
+    let ext = temp.path().join("provided_ext.so");
+    fs::write(&ext, read_wheel_entry(output, "mypkg/_ext.so")).unwrap();
+    assert!(parse_needed(&ext).contains("libbundled.so.2"));
+    let binary = lief::elf::Binary::parse(&ext).expect("failed to parse ELF");
+    assert!(unrepair::elf::dynamic::extract_search_paths(&binary)
+        .contains(&"$ORIGIN/../provider/lib".to_string()));
+    let metadata = String::from_utf8(read_wheel_entry(
+        output,
+        "mypkg-1.0+unrepaired.dist-info/METADATA",
+    ))
+    .unwrap();
+    assert!(metadata.contains("Requires-Dist: provider==1.0\n"));
+    assert!(!metadata.contains("Requires-External"));
+    let json = read_wheel_entry(output, "mypkg-1.0+unrepaired.dist-info/unrepair.json");
+    let provenance: unrepair::wheel::Provenance =
+        serde_json::from_slice(&json).expect("unrepair.json parses");
+    assert_eq!(
+        provenance.replacements[0].runpath.as_deref(),
+        Some("$ORIGIN/../provider/lib")
+    );
+    assert_eq!(
+        provenance.system_libraries[0].provider.as_deref(),
+        Some("provider==1.0")
+    );
+}
+
+#[test]
+fn revendor_restores_a_self_contained_wheel() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let out_dir = temp.path().join("out");
+    fs::create_dir_all(&out_dir).unwrap();
+    let unvendored = out_dir.join("mypkg-1.0+unrepaired-cp312-cp312-linux_x86_64.whl");
+    unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .retag(unrepair::wheel::Retag::Platform("linux_x86_64".to_string()))
+        .output_wheel(&unvendored)
+        .run()
+        .expect("wheel workflow failed");
+
+    // WHEN
+    let result = unrepair::Unrepair::revendor(&unvendored)
+        .options(unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir))
+        .run()
+        .expect("revendor failed");
+
+    // THEN
+    assert!(result.from_provenance);
+    let output = out_dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    assert_eq!(Path::new(&result.output_wheel), output);
+    assert_eq!(result.libraries.len(), 1);
+    let lib = &result.libraries[0];
+    assert_eq!(lib.soname, "libbundled.so.2");
+    assert_eq!(
+        Path::new(&lib.system_path),
+        system_dir.join("libbundled.so.2")
+    );
+    assert_eq!(lib.extensions, ["mypkg/_ext.so"]);
+    let copy_name = lib.path.strip_prefix("mypkg.libs/").unwrap();
+    assert!(copy_name.starts_with("libbundled-") && copy_name.ends_with(".so.2"));
+
+    let entries = read_wheel_entries(&output);
+    assert!(entries.contains(&lib.path));
+    assert!(!entries.iter().any(|name| name.ends_with("unrepair.json")));
+    let metadata =
+        String::from_utf8(read_wheel_entry(&output, "mypkg-1.0.dist-info/METADATA")).unwrap();
+    assert!(metadata.contains("Version: 1.0\n"));
+    assert!(!metadata.contains("Requires-External"));
+    let record =
+        String::from_utf8(read_wheel_entry(&output, "mypkg-1.0.dist-info/RECORD")).unwrap();
+    let contents = read_wheel_entry(&output, &lib.path);
+    assert!(record.contains(&record_line(&lib.path, &contents)));
+
+    let copy = temp.path().join("revendored.so");
+    fs::write(&copy, contents).unwrap();
+    assert_eq!(
+        unrepair::elf::soname::extract_soname(&copy)
+            .unwrap()
+            .as_deref(),
+        Some(copy_name)
+    );
+    let ext = temp.path().join("revendored_ext.so");
+    fs::write(&ext, read_wheel_entry(&output, "mypkg/_ext.so")).unwrap();
+    assert!(parse_needed(&ext).contains(copy_name));
+    let binary = lief::elf::Binary::parse(&ext).expect("failed to parse ELF");
+    assert!(unrepair::elf::dynamic::extract_search_paths(&binary)
+        .contains(&"$ORIGIN/../mypkg.libs".to_string()));
+}
+
+#[test]
+fn prune_unused_removes_unreachable_bundled_libraries() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let dir = temp.path();
+    let (ext, bundled, _system) = build_case(
+        &temp,
+        "int add(int a, int b) { return a + b; }\n\
+         int multiply(int a, int b) { return a * b; }\n\
+         const char* get_name(void) { return \"bundled\"; }\n",
+        "int add(int a, int b) { return a + b; }\n",
+        "libbundled.so",
+        "libbundled.so.2",
+        None,
+        None,
+    );
+    let loader_c = dir.join("loader.c");
+    write_file(
+        &loader_c,
+        r#"
+            extern int add(int a, int b);
+            const char* plugin_name(void) { return "libplugin.so.1"; }
+            int loader_func(void) { return add(1, 2); }
+        "#,
+    );
+    let loader = dir.join("loader.so");
+    compile_extension(&loader_c, &loader, dir, "bundled");
+    let leaf_c = dir.join("leaf.c");
+    write_file(&leaf_c, "int leaf(void) { return 1; }\n");
+    let plugin = dir.join("libplugin.so.1");
+    compile_shared(&leaf_c, &plugin, "libplugin.so.1", None);
+    let unused = dir.join("libunused.so.3");
+    compile_shared(&leaf_c, &unused, "libunused.so.3", None);
+
+    let wheel = dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = vec![
+        ("mypkg/__init__.py", b"".to_vec()),
+        ("mypkg/_ext.so", fs::read(&ext).unwrap()),
+        ("mypkg/_loader.so", fs::read(&loader).unwrap()),
+        ("mypkg.libs/libbundled.so", fs::read(&bundled).unwrap()),
+        ("mypkg.libs/libplugin.so.1", fs::read(&plugin).unwrap()),
+        ("mypkg.libs/libunused.so.3", fs::read(&unused).unwrap()),
+        (
+            "mypkg-1.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0\n".to_vec(),
+        ),
+        (
+            "mypkg-1.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .prune_unused(true)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete());
+    assert!(result.pairs.is_empty());
+    assert_eq!(result.removed_bundled_paths, ["mypkg.libs/libunused.so.3"]);
+    assert_eq!(result.summary.pruned_bundled_libs, 1);
+    assert_eq!(
+        result.summary.pruned_bytes,
+        fs::metadata(&unused).unwrap().len()
+    );
+    let entries = read_wheel_entries(Path::new(&result.output_wheel));
+    assert!(entries.contains(&"mypkg.libs/libbundled.so".to_string()));
+    assert!(entries.contains(&"mypkg.libs/libplugin.so.1".to_string()));
+    assert!(!entries.iter().any(|name| name.contains("libunused")));
+}
+
+#[test]
+fn wheel_objects_are_found_by_elf_magic_and_all_consumers_are_patched() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (_, system_dir) = build_wheel_case(&temp);
+    let dir = temp.path();
+    let core_c = dir.join("core.c");
+    write_file(
+        &core_c,
+        r#"
+            extern int add(int a, int b);
+            void* PyInit__core(void) { return (void*)0; }
+            int core_func(void) { return add(1, 2); }
+        "#,
+    );
+    let core = dir.join("core.so");
+    compile_extension(&core_c, &core, dir, "bundled");
+    let tool_c = dir.join("tool.c");
+    write_file(
+        &tool_c,
+        r#"
+            extern int add(int a, int b);
+            int main(void) { return add(1, 2) == 3 ? 0 : 1; }
+        "#,
+    );
+    let tool = dir.join("tool");
+    run(Command::new("cc")
+        .arg(&tool_c)
+        .arg("-L")
+        .arg(dir)
+        .arg("-lbundled")
+        .arg(format!("-Wl,-rpath,{}", dir.to_string_lossy()))
+        .arg("-o")
+        .arg(&tool));
+
+    let wheel = dir.join("mypkg-1.0-cp312-cp312-linux_x86_64.whl");
+    let entries = vec![
+        ("mypkg/__init__.py", b"".to_vec()),
+        (
+            "mypkg/_core.cpython-312-x86_64-linux-gnu.so",
+            fs::read(&core).unwrap(),
+        ),
+        (
+            "mypkg/plugins/helper.so",
+            fs::read(dir.join("ext.so")).unwrap(),
+        ),
+        ("mypkg/bin/tool", fs::read(&tool).unwrap()),
+        ("mypkg/truncated.so", b"\x7fELF\x02\x01".to_vec()),
+        (
+            "mypkg.libs/libbundled.so",
+            fs::read(dir.join("libbundled.so")).unwrap(),
+        ),
+        (
+            "mypkg-1.0.dist-info/METADATA",
+            b"Metadata-Version: 2.1\nName: mypkg\nVersion: 1.0\n".to_vec(),
+        ),
+        (
+            "mypkg-1.0.dist-info/WHEEL",
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: false\nTag: cp312-cp312-linux_x86_64\n"
+                .to_vec(),
+        ),
+    ];
+    write_wheel(&wheel, &with_record(entries, "mypkg-1.0.dist-info/RECORD"));
+
+    // WHEN
+    let result = unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .run()
+        .expect("wheel workflow failed");
+
+    // THEN
+    assert!(result.is_complete(), "{:?}", result.failures);
+    assert_eq!(result.pairs.len(), 1);
+    assert_eq!(result.pairs[0].patched_extensions, 3);
+    assert_eq!(result.removed_bundled_paths, ["mypkg.libs/libbundled.so"]);
+    let output = Path::new(&result.output_wheel);
+    for name in [
+        "mypkg/_core.cpython-312-x86_64-linux-gnu.so",
+        "mypkg/plugins/helper.so",
+        "mypkg/bin/tool",
+    ] {
+        let patched = dir.join("patched");
+        fs::write(&patched, read_wheel_entry(output, name)).unwrap();
+        let needed = parse_needed(&patched);
+        assert!(needed.contains("libbundled.so.2"), "{name}: {needed:?}");
+        assert!(!needed.contains("libbundled.so"), "{name}: {needed:?}");
+    }
+}
+
+#[test]
+fn revendor_also_vendors_dependencies_of_system_libraries() {
+    require_build_tools();
+
+    // GIVEN
+    let temp = TempDir::new().expect("failed to create tempdir");
+    let (wheel, system_dir) = build_wheel_case(&temp);
+    let dir = temp.path();
+    let dep_c = dir.join("dep.c");
+    write_file(&dep_c, "int dep_value(void) { return 7; }\n");
+    compile_shared(&dep_c, &system_dir.join("libdep.so"), "libdep.so.1", None);
+    let system_c = dir.join("system_with_dep.c");
+    write_file(
+        &system_c,
+        r#"
+            extern int dep_value(void);
+            int add(int a, int b) { return a + b + dep_value() - 7; }
+            int multiply(int a, int b) { return a * b; }
+            const char* get_name(void) { return "system"; }
+        "#,
+    );
+    run(Command::new("cc")
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg(&system_c)
+        .arg("-L")
+        .arg(&system_dir)
+        .arg("-ldep")
+        .arg("-Wl,-soname,libbundled.so.2")
+        .arg("-o")
+        .arg(system_dir.join("libbundled.so.2")));
+    let out_dir = dir.join("out");
+    fs::create_dir_all(&out_dir).unwrap();
+    let unvendored = out_dir.join("mypkg-1.0+unrepaired-cp312-cp312-linux_x86_64.whl");
+    unrepair::Unrepair::wheel(&wheel)
+        .system_lib_dir(&system_dir)
+        .output_wheel(&unvendored)
+        .run()
+        .expect("wheel workflow failed");
+
+    // WHEN
+    let result = unrepair::Unrepair::revendor(&unvendored)
+        .options(unrepair::wheel::WheelBuilder::default().system_lib_dir(&system_dir))
+        .run()
+        .expect("revendor failed");
+
+    // THEN
+    assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    let sonames = result
+        .libraries
+        .iter()
+        .map(|lib| lib.soname.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(sonames, ["libbundled.so.2", "libdep.so.1"]);
+    let (main, dep) = (&result.libraries[0], &result.libraries[1]);
+    assert_eq!(dep.extensions, std::slice::from_ref(&main.path));
+
+    let copy = dir.join("revendored_main.so");
+    fs::write(
+        &copy,
+        read_wheel_entry(Path::new(&result.output_wheel), &main.path),
+    )
+    .unwrap();
+    let dep_name = dep.path.strip_prefix("mypkg.libs/").unwrap();
+    assert!(parse_needed(&copy).contains(dep_name));
+}